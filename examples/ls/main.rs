@@ -1,8 +1,8 @@
-/// display the content of the current directory
+//! display the content of the current directory
 
 use {
     std::{env, io, path::PathBuf},
-    umask::Mode,
+    umask::FullMode,
 };
 
 fn list_files() -> io::Result<()> {
@@ -14,7 +14,7 @@ fn list_files() -> io::Result<()> {
         .collect();
     paths.sort_unstable();
     for path in paths {
-        let mode = Mode::try_from(&path)?;
+        let mode = FullMode::try_from(&path)?;
         let name = path.file_name().unwrap().to_string_lossy();
         println!("{}  {}", mode, name);
     }
@@ -0,0 +1,410 @@
+use {
+    crate::*,
+    std::fmt::{self, Display, Formatter},
+};
+
+#[cfg(unix)]
+use std::{fs, os::unix::fs::MetadataExt};
+
+/// The identity a process uses for permission checks:
+/// effective uid, effective gid and supplementary groups
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Identity {
+    pub uid: u32,
+    pub gid: u32,
+    pub groups: Vec<u32>,
+}
+
+impl Identity {
+    pub fn new(uid: u32, gid: u32) -> Self {
+        Self {
+            uid,
+            gid,
+            groups: Vec::new(),
+        }
+    }
+    pub fn with_groups(mut self, groups: Vec<u32>) -> Self {
+        self.groups = groups;
+        self
+    }
+    /// Return the identity of the current process
+    #[cfg(unix)]
+    pub fn current() -> Self {
+        let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+        let mut groups = Vec::new();
+        let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+        if count > 0 {
+            groups.resize(count as usize, 0);
+            let count = unsafe { libc::getgroups(count, groups.as_mut_ptr()) };
+            groups.truncate(count.max(0) as usize);
+        }
+        Self { uid, gid, groups }
+    }
+    /// Tell whether the gid is the primary or a supplementary group
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+}
+
+/// What's needed to evaluate access to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileStat {
+    pub file_type: FileType,
+    pub mode: Mode,
+    /// uid of the owner
+    pub uid: u32,
+    /// gid of the group
+    pub gid: u32,
+}
+
+impl FileStat {
+    pub fn new(file_type: FileType, mode: Mode, uid: u32, gid: u32) -> Self {
+        Self {
+            file_type,
+            mode,
+            uid,
+            gid,
+        }
+    }
+    /// Read the stat of the given path, following symbolic links
+    #[cfg(unix)]
+    pub fn read<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self::from(&metadata))
+    }
+}
+
+#[cfg(unix)]
+impl From<&fs::Metadata> for FileStat {
+    fn from(metadata: &fs::Metadata) -> Self {
+        let full_mode = FullMode::from(metadata);
+        Self {
+            file_type: full_mode.file_type,
+            mode: full_mode.mode,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        }
+    }
+}
+
+/// A Linux capability bypassing the file permission checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Bypasses read, write and execute checks, except that
+    /// executing a file still needs at least one execute bit
+    DacOverride,
+    /// Bypasses read checks on files, and read and search
+    /// checks on directories
+    DacReadSearch,
+    /// Bypasses the checks requiring to be the owner of a file,
+    /// like the restriction of deletion in sticky directories
+    Fowner,
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::DacOverride => "CAP_DAC_OVERRIDE",
+            Self::DacReadSearch => "CAP_DAC_READ_SEARCH",
+            Self::Fowner => "CAP_FOWNER",
+        })
+    }
+}
+
+/// The capabilities of a process which matter for file access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Capabilities {
+    pub dac_override: bool,
+    pub dac_read_search: bool,
+    pub fowner: bool,
+}
+
+impl Capabilities {
+    pub const NONE: Self = Self {
+        dac_override: false,
+        dac_read_search: false,
+        fowner: false,
+    };
+    /// The capabilities of an unrestricted root process
+    pub const ALL: Self = Self {
+        dac_override: true,
+        dac_read_search: true,
+        fowner: true,
+    };
+    pub fn has(self, capability: Capability) -> bool {
+        match capability {
+            Capability::DacOverride => self.dac_override,
+            Capability::DacReadSearch => self.dac_read_search,
+            Capability::Fowner => self.fowner,
+        }
+    }
+}
+
+/// What decided the access
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccessReason {
+    /// The identity owns the file, so the user class applies
+    Owner { uid: u32 },
+    /// The identity is in the file's group, so the group class applies
+    GroupMember { gid: u32, supplementary: bool },
+    /// The identity neither owns the file nor is in its group,
+    /// so the others class applies
+    Other { uid: u32, gid: u32 },
+}
+
+impl AccessReason {
+    /// The class ([`USER`], [`GROUP`] or [`OTHERS`]) whose permissions apply
+    pub fn class(self) -> Class {
+        match self {
+            Self::Owner { .. } => USER,
+            Self::GroupMember { .. } => GROUP,
+            Self::Other { .. } => OTHERS,
+        }
+    }
+}
+
+/// The effective rights of an identity on a file, and why
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Access {
+    pub read: bool,
+    pub write: bool,
+    pub exec: bool,
+    pub reason: AccessReason,
+    /// The capability which granted rights the class didn't give, if any
+    pub bypass: Option<Capability>,
+    /// The mode of the evaluated file
+    pub mode: Mode,
+}
+
+impl Access {
+    /// Tell whether all the wanted permissions (a combination
+    /// of [`READ`], [`WRITE`] and [`EXEC`]) are granted
+    pub fn allows(&self, wanted: Permission) -> bool {
+        (self.read || wanted & READ == 0)
+            && (self.write || wanted & WRITE == 0)
+            && (self.exec || wanted & EXEC == 0)
+    }
+    /// The granted permissions, in the `rwx` form
+    pub fn rwx(&self) -> String {
+        format!(
+            "{}{}{}",
+            if self.read { 'r' } else { '-' },
+            if self.write { 'w' } else { '-' },
+            if self.exec { 'x' } else { '-' },
+        )
+    }
+}
+
+impl Display for Access {
+    /// Explains the access, e.g. `"r-x: gid 100 is the file's group
+    /// and the primary group, so the group class applies"`
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.rwx())?;
+        match self.reason {
+            AccessReason::Owner { uid } => {
+                write!(f, "uid {} is the owner, so the user class applies", uid)
+            }
+            AccessReason::GroupMember { gid, supplementary } => write!(
+                f,
+                "gid {} is the {} group, so the group class applies",
+                gid,
+                if supplementary {
+                    "file's group and a supplementary"
+                } else {
+                    "file's group and the primary"
+                },
+            ),
+            AccessReason::Other { uid, gid } => write!(
+                f,
+                "uid {} isn't the owner and isn't in group {}, so the others class applies",
+                uid, gid
+            ),
+        }?;
+        if let Some(capability) = self.bypass {
+            write!(f, ", but {} bypasses the permission checks", capability)?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the effective access rights of an identity to files,
+/// with the class selection rule of the kernel: the user class
+/// if the identity owns the file, else the group class if it's in
+/// the file's group, else the others class. There's no fallthrough:
+/// an owner without read permission can't read, even when others can.
+///
+/// By default, the evaluator only looks at the class permissions. Use
+/// [`AccessEvaluator::privilege_aware`] or [`AccessEvaluator::with_capabilities`]
+/// to model privileged processes:
+///
+/// ```
+/// use umask::*;
+///
+/// let root = AccessEvaluator::privilege_aware(Identity::new(0, 0));
+/// let file = FileStat::new(FileType::Regular, Mode::from(0o600), 1000, 1000);
+/// assert!(root.evaluate(&file).allows(READ | WRITE));
+/// assert!(!root.evaluate(&file).allows(EXEC)); // there's no execute bit
+/// ```
+///
+/// ```
+/// use umask::*;
+///
+/// let evaluator = AccessEvaluator::new(Identity::new(1000, 1000).with_groups(vec![100]));
+/// let file = FileStat::new(FileType::Regular, Mode::from(0o640), 0, 100);
+/// let access = evaluator.evaluate(&file);
+/// assert!(access.allows(READ));
+/// assert!(!access.allows(READ | WRITE));
+/// assert_eq!(access.reason.class(), GROUP);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessEvaluator {
+    identity: Identity,
+    capabilities: Capabilities,
+}
+
+impl AccessEvaluator {
+    /// Build an evaluator checking only the class permissions,
+    /// as for an unprivileged process
+    pub fn new(identity: Identity) -> Self {
+        Self {
+            identity,
+            capabilities: Capabilities::NONE,
+        }
+    }
+    /// Build an evaluator giving to root (uid 0) the capabilities
+    /// of an unrestricted root process
+    pub fn privilege_aware(identity: Identity) -> Self {
+        let capabilities = if identity.uid == 0 {
+            Capabilities::ALL
+        } else {
+            Capabilities::NONE
+        };
+        Self {
+            identity,
+            capabilities,
+        }
+    }
+    /// Set the capabilities of the process (for example a non root
+    /// daemon with CAP_DAC_READ_SEARCH, or a root one without any)
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+    pub fn evaluate(&self, file: &FileStat) -> Access {
+        let id = &self.identity;
+        let reason = if id.uid == file.uid {
+            AccessReason::Owner { uid: id.uid }
+        } else if id.in_group(file.gid) {
+            AccessReason::GroupMember {
+                gid: file.gid,
+                supplementary: id.gid != file.gid,
+            }
+        } else {
+            AccessReason::Other {
+                uid: id.uid,
+                gid: file.gid,
+            }
+        };
+        let class = reason.class();
+        let mut access = Access {
+            read: file.mode.has(Mode::from(class & READ)),
+            write: file.mode.has(Mode::from(class & WRITE)),
+            exec: file.mode.has(Mode::from(class & EXEC)),
+            reason,
+            bypass: None,
+            mode: file.mode,
+        };
+        let caps = self.capabilities;
+        let read_cap = if caps.dac_read_search {
+            Some(Capability::DacReadSearch)
+        } else if caps.dac_override {
+            Some(Capability::DacOverride)
+        } else {
+            None
+        };
+        let write_cap = if caps.dac_override {
+            Some(Capability::DacOverride)
+        } else {
+            None
+        };
+        let exec_cap = if file.file_type == FileType::Directory {
+            read_cap // searching a directory
+        } else if file.mode.has_any(Mode::from(EXEC)) {
+            write_cap
+        } else {
+            None
+        };
+        for (granted, cap) in [
+            (&mut access.read, read_cap),
+            (&mut access.write, write_cap),
+            (&mut access.exec, exec_cap),
+        ] {
+            if let (false, Some(cap)) = (*granted, cap) {
+                *granted = true;
+                // CAP_DAC_OVERRIDE is the one reported when both are used
+                if access.bypass != Some(Capability::DacOverride) {
+                    access.bypass = Some(cap);
+                }
+            }
+        }
+        access
+    }
+}
+
+#[test]
+fn test_class_selection() {
+    let file = FileStat::new(FileType::Regular, Mode::from(0o047), 1000, 100);
+    // the owner gets nothing, even if the group and others can read
+    let owner = AccessEvaluator::new(Identity::new(1000, 100)).evaluate(&file);
+    assert_eq!(owner.rwx(), "---");
+    assert_eq!(owner.reason, AccessReason::Owner { uid: 1000 });
+    // group members can read but can't write, even if others can
+    let member = AccessEvaluator::new(Identity::new(1001, 1001).with_groups(vec![100]));
+    let access = member.evaluate(&file);
+    assert_eq!(access.rwx(), "r--");
+    assert_eq!(
+        access.to_string(),
+        "r--: gid 100 is the file's group and a supplementary group, so the group class applies"
+    );
+    let other = AccessEvaluator::new(Identity::new(1002, 1002)).evaluate(&file);
+    assert_eq!(other.rwx(), "rwx");
+    assert_eq!(other.reason.class(), OTHERS);
+    assert!(other.allows(READ | WRITE | EXEC));
+}
+
+#[test]
+fn test_privileges() {
+    let file = FileStat::new(FileType::Regular, Mode::from(0o600), 1000, 1000);
+    let script = FileStat::new(FileType::Regular, Mode::from(0o744), 1000, 1000);
+    let dir = FileStat::new(FileType::Directory, Mode::from(0o700), 1000, 1000);
+    // root is just another user without privilege awareness
+    let root = AccessEvaluator::new(Identity::new(0, 0));
+    assert_eq!(root.evaluate(&file).rwx(), "---");
+    let root = AccessEvaluator::privilege_aware(Identity::new(0, 0));
+    let access = root.evaluate(&file);
+    assert_eq!(access.rwx(), "rw-");
+    assert_eq!(access.bypass, Some(Capability::DacOverride));
+    assert_eq!(
+        access.to_string(),
+        "rw-: uid 0 isn't the owner and isn't in group 1000, so the others class applies, \
+        but CAP_DAC_OVERRIDE bypasses the permission checks"
+    );
+    assert_eq!(root.evaluate(&script).rwx(), "rwx");
+    assert_eq!(root.evaluate(&dir).rwx(), "rwx");
+    let reader =
+        AccessEvaluator::privilege_aware(Identity::new(33, 33)).with_capabilities(Capabilities {
+            dac_read_search: true,
+            ..Capabilities::NONE
+        });
+    let access = reader.evaluate(&file);
+    assert_eq!(access.rwx(), "r--");
+    assert_eq!(access.bypass, Some(Capability::DacReadSearch));
+    assert_eq!(reader.evaluate(&script).rwx(), "r--");
+    assert_eq!(reader.evaluate(&dir).rwx(), "r-x");
+}
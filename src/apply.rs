@@ -0,0 +1,148 @@
+use {
+    crate::*,
+    std::{
+        ffi::CString,
+        fmt::{self, Display, Formatter},
+        fs::{self, File, Permissions},
+        io,
+        os::unix::{
+            ffi::OsStrExt,
+            fs::PermissionsExt,
+            io::{AsRawFd, RawFd},
+        },
+        path::{Path, PathBuf},
+    },
+    thiserror::Error,
+};
+
+/// The system call used to change a mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChmodOperation {
+    Chmod,
+    Fchmod,
+    Fchmodat,
+}
+
+impl Display for ChmodOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Chmod => "chmod",
+            Self::Fchmod => "fchmod",
+            Self::Fchmodat => "fchmodat",
+        })
+    }
+}
+
+/// Error returned when applying a mode failed
+#[derive(Debug, Error)]
+#[error("{operation} to {mode:o}{} failed: {source}", path_text(.path))]
+pub struct ChmodError {
+    pub operation: ChmodOperation,
+    /// The path whose mode was to be changed, if known
+    /// (it isn't when only a file descriptor was given)
+    pub path: Option<PathBuf>,
+    pub mode: Mode,
+    pub source: io::Error,
+}
+
+fn path_text(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => format!(" of {:?}", path),
+        None => String::new(),
+    }
+}
+
+impl Mode {
+    /// Return the permission and extra permission bits, as
+    /// accepted by chmod(2) (file type bits are removed)
+    fn chmod_bits(self) -> u32 {
+        u32::from(self) & (EXTRA | ALL)
+    }
+    /// Set this mode on the file at the given path, following symbolic links
+    pub fn apply_to<P: AsRef<Path>>(self, path: P) -> Result<(), ChmodError> {
+        let path = path.as_ref();
+        fs::set_permissions(path, Permissions::from_mode(self.chmod_bits())).map_err(|source| {
+            ChmodError {
+                operation: ChmodOperation::Chmod,
+                path: Some(path.to_path_buf()),
+                mode: self,
+                source,
+            }
+        })
+    }
+    /// Set this mode on an open file, using fchmod(2)
+    pub fn apply_to_file(self, file: &File) -> Result<(), ChmodError> {
+        file.set_permissions(Permissions::from_mode(self.chmod_bits()))
+            .map_err(|source| ChmodError {
+                operation: ChmodOperation::Fchmod,
+                path: None,
+                mode: self,
+                source,
+            })
+    }
+    /// Set this mode on the file of the given name relative to
+    /// an open directory, using fchmodat(2).
+    ///
+    /// When `follow_symlinks` is false and the target is a symbolic
+    /// link, Linux returns an error, as the mode of a link can't be changed.
+    pub fn apply_at<D: AsRawFd, P: AsRef<Path>>(
+        self,
+        dir: &D,
+        name: P,
+        follow_symlinks: bool,
+    ) -> Result<(), ChmodError> {
+        let name = name.as_ref();
+        let err = |source| ChmodError {
+            operation: ChmodOperation::Fchmodat,
+            path: Some(name.to_path_buf()),
+            mode: self,
+            source,
+        };
+        let c_name = CString::new(name.as_os_str().as_bytes())
+            .map_err(|e| err(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+        let flags = if follow_symlinks {
+            0
+        } else {
+            libc::AT_SYMLINK_NOFOLLOW
+        };
+        let dir_fd: RawFd = dir.as_raw_fd();
+        let res = unsafe {
+            libc::fchmodat(
+                dir_fd,
+                c_name.as_ptr(),
+                self.chmod_bits() as libc::mode_t,
+                flags,
+            )
+        };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(err(io::Error::last_os_error()))
+        }
+    }
+}
+
+#[test]
+fn test_apply() {
+    let dir = TestDir::new("apply");
+    let path = dir.join("file");
+    let file = File::create(&path).unwrap();
+    let read = |path: &Path| FullMode::try_from(path).unwrap().mode;
+
+    Mode::from(0o600).apply_to(&path).unwrap();
+    assert_eq!(read(&path), Mode::from(0o600));
+
+    Mode::from(0o640).apply_to_file(&file).unwrap();
+    assert_eq!(read(&path), Mode::from(0o640));
+
+    let dir_file = File::open(&dir).unwrap();
+    Mode::from(0o4755)
+        .apply_at(&dir_file, "file", true)
+        .unwrap();
+    assert_eq!(read(&path), Mode::from(0o4755));
+
+    let err = Mode::from(0o644).apply_to(dir.join("missing")).unwrap_err();
+    assert_eq!(err.operation, ChmodOperation::Chmod);
+    assert!(err.to_string().starts_with("chmod to 644 of "));
+    assert_eq!(err.source.kind(), io::ErrorKind::NotFound);
+}
@@ -0,0 +1,369 @@
+use {
+    crate::*,
+    std::{
+        collections::HashMap,
+        fmt::{self, Display, Formatter},
+        path::{Path, PathBuf},
+    },
+};
+
+/// How risky a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// A check done by an [`Audit`] on every entry of a tree
+/// (symbolic links excepted)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuditRule {
+    /// A regular file anybody can write (devices, fifos and
+    /// sockets are commonly writable by anybody, e.g. `/dev/null`)
+    WorldWritable,
+    /// A directory anybody can write, without the sticky bit, so
+    /// that anybody can delete or replace the files of others
+    WorldWritableDir,
+    /// An executable with the setuid or setgid bit
+    SetIdExecutable,
+    /// A setuid, setgid or sticky bit without the matching exec
+    /// bit, displayed as `S` or `T`
+    ExtraWithoutExec,
+    /// A class which can write but not read
+    WriteWithoutRead,
+    /// A group writable regular file in a directory which isn't shared,
+    /// a shared directory being one with the setgid bit
+    GroupWritableInPrivateDir,
+    /// A file with an exec bit which starts neither with a
+    /// shebang nor with the ELF magic number
+    ExecWithoutMagic,
+}
+
+impl AuditRule {
+    pub const ALL: &'static [Self] = &[
+        Self::WorldWritable,
+        Self::WorldWritableDir,
+        Self::SetIdExecutable,
+        Self::ExtraWithoutExec,
+        Self::WriteWithoutRead,
+        Self::GroupWritableInPrivateDir,
+        Self::ExecWithoutMagic,
+    ];
+    /// The identifier of the rule, e.g. `"world-writable"`
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::WorldWritable => "world-writable",
+            Self::WorldWritableDir => "world-writable-dir",
+            Self::SetIdExecutable => "setid-executable",
+            Self::ExtraWithoutExec => "extra-without-exec",
+            Self::WriteWithoutRead => "write-without-read",
+            Self::GroupWritableInPrivateDir => "group-writable-in-private-dir",
+            Self::ExecWithoutMagic => "exec-without-magic",
+        }
+    }
+    /// Return the rule with the given identifier
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|rule| rule.id() == id)
+    }
+    pub fn severity(self) -> Severity {
+        match self {
+            Self::WorldWritable | Self::WorldWritableDir => Severity::Error,
+            Self::SetIdExecutable
+            | Self::ExtraWithoutExec
+            | Self::GroupWritableInPrivateDir
+            | Self::ExecWithoutMagic => Severity::Warning,
+            Self::WriteWithoutRead => Severity::Info,
+        }
+    }
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::WorldWritable => "the file is writable by anybody",
+            Self::WorldWritableDir => "the directory is writable by anybody and isn't sticky",
+            Self::SetIdExecutable => "the executable is setuid or setgid",
+            Self::ExtraWithoutExec => "a setuid, setgid or sticky bit is set without exec bit",
+            Self::WriteWithoutRead => "a class can write but not read",
+            Self::GroupWritableInPrivateDir => {
+                "the file is group writable but its directory isn't setgid"
+            }
+            Self::ExecWithoutMagic => {
+                "the file is executable but has neither shebang nor ELF magic"
+            }
+        }
+    }
+}
+
+impl Display for AuditRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.id())
+    }
+}
+
+/// A rule broken by an entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub path: PathBuf,
+    pub rule: AuditRule,
+    pub severity: Severity,
+    pub full_mode: FullMode,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} [{}] {:?} ({}): {}",
+            self.severity,
+            self.rule,
+            self.path,
+            self.full_mode,
+            self.rule.description(),
+        )
+    }
+}
+
+/// The result of an audit
+#[derive(Debug, Default)]
+pub struct AuditReport {
+    pub findings: Vec<Finding>,
+    pub errors: Vec<TreeError>,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty() && self.errors.is_empty()
+    }
+    /// Return the findings of at least the given severity
+    pub fn at_least(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |f| f.severity >= severity)
+    }
+}
+
+/// Checks the modes of the entries of a tree against lint rules,
+/// all of them being enabled by default.
+///
+/// ```no_run
+/// use umask::*;
+///
+/// let report = Audit::new()
+///     .disable(AuditRule::WriteWithoutRead)
+///     .run("/srv/app");
+/// for finding in report.at_least(Severity::Warning) {
+///     println!("{}", finding);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Audit {
+    rules: Vec<AuditRule>,
+    follow_symlinks: bool,
+}
+
+impl Default for Audit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Audit {
+    pub fn new() -> Self {
+        Self {
+            rules: AuditRule::ALL.to_vec(),
+            follow_symlinks: false,
+        }
+    }
+    pub fn disable(mut self, rule: AuditRule) -> Self {
+        self.rules.retain(|&r| r != rule);
+        self
+    }
+    pub fn enable(mut self, rule: AuditRule) -> Self {
+        if !self.rules.contains(&rule) {
+            self.rules.push(rule);
+        }
+        self
+    }
+    pub fn is_enabled(&self, rule: AuditRule) -> bool {
+        self.rules.contains(&rule)
+    }
+    /// Follow the symbolic links (by default, they're neither
+    /// checked nor traversed)
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+    /// Return the rules broken by an entry, `parent_mode` being the mode of
+    /// the directory containing it, if known.
+    ///
+    /// The [`AuditRule::ExecWithoutMagic`] rule isn't checked here as
+    /// it needs the content of the file.
+    pub fn check_mode(&self, full_mode: FullMode, parent_mode: Option<Mode>) -> Vec<AuditRule> {
+        let FullMode { file_type, mode } = full_mode;
+        let is_dir = file_type == FileType::Directory;
+        let is_file = file_type == FileType::Regular;
+        let mut broken = Vec::new();
+        if file_type == FileType::Symlink {
+            return broken;
+        }
+        let mut check = |rule, condition: bool| {
+            if condition && self.is_enabled(rule) {
+                broken.push(rule);
+            }
+        };
+        check(AuditRule::WorldWritable, is_file && mode.has(OTHERS_WRITE));
+        check(
+            AuditRule::WorldWritableDir,
+            is_dir && mode.has(OTHERS_WRITE) && !mode.has_extra(STICKY),
+        );
+        check(
+            AuditRule::SetIdExecutable,
+            is_file && mode.is_exe() && mode.has_any(Mode::from(SETUID | SETGID)),
+        );
+        check(
+            AuditRule::ExtraWithoutExec,
+            (mode.has_extra(SETUID) && !mode.has(USER_EXEC))
+                || (mode.has_extra(SETGID) && !mode.has(GROUP_EXEC))
+                || (mode.has_extra(STICKY) && !mode.has(OTHERS_EXEC)),
+        );
+        check(
+            AuditRule::WriteWithoutRead,
+            [USER, GROUP, OTHERS].iter().any(|&class| {
+                mode.has(Mode::from(class & WRITE)) && !mode.has(Mode::from(class & READ))
+            }),
+        );
+        check(
+            AuditRule::GroupWritableInPrivateDir,
+            is_file
+                && mode.has(GROUP_WRITE)
+                && parent_mode.is_some_and(|parent| !parent.has_extra(SETGID)),
+        );
+        broken
+    }
+    /// Check all the entries of the tree
+    pub fn run<P: AsRef<Path>>(&self, root: P) -> AuditReport {
+        let mut report = AuditReport::default();
+        let mut dir_modes: HashMap<PathBuf, Mode> = HashMap::new();
+        let walker = TreeWalker::new(root).follow_symlinks(self.follow_symlinks);
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    report.errors.push(e);
+                    continue;
+                }
+            };
+            let full_mode = entry.full_mode();
+            if entry.is_dir() {
+                dir_modes.insert(entry.path.clone(), full_mode.mode);
+            }
+            let parent_mode = entry
+                .path
+                .parent()
+                .and_then(|parent| dir_modes.get(parent))
+                .copied();
+            let mut broken = self.check_mode(full_mode, parent_mode);
+            if self.is_enabled(AuditRule::ExecWithoutMagic)
+                && full_mode.file_type == FileType::Regular
+                && full_mode.mode.is_exe()
+            {
+                let mut start = [0; 4];
+                match read_start(&entry.path, &mut start) {
+                    Ok(start) => {
+                        if !(start.starts_with(b"#!") || start == b"\x7fELF") {
+                            broken.push(AuditRule::ExecWithoutMagic);
+                        }
+                    }
+                    Err(source) => report.errors.push(TreeError::Read {
+                        path: entry.path.clone(),
+                        source,
+                    }),
+                }
+            }
+            for rule in broken {
+                report.findings.push(Finding {
+                    path: entry.path.clone(),
+                    rule,
+                    severity: rule.severity(),
+                    full_mode,
+                });
+            }
+        }
+        report
+    }
+}
+
+#[test]
+fn test_audit() {
+    use std::fs;
+    let root = TestDir::new("audit");
+    fs::create_dir_all(root.join("shared")).unwrap();
+    fs::create_dir_all(root.join("tmp")).unwrap();
+    fs::write(root.join("script"), "#!/bin/sh\n").unwrap();
+    fs::write(root.join("notes"), "").unwrap();
+    fs::write(root.join("shared/doc"), "").unwrap();
+    fs::write(root.join("doc"), "").unwrap();
+    for (path, mode) in [
+        ("", 0o755),
+        ("shared", 0o2775),
+        ("tmp", 0o777),
+        ("script", 0o4755),
+        ("notes", 0o0746),
+        ("shared/doc", 0o664),
+        ("doc", 0o620),
+    ] {
+        Mode::from(mode).apply_to(root.join(path)).unwrap();
+    }
+    let found = |audit: &Audit| -> Vec<String> {
+        let report = audit.run(&root);
+        assert!(report.errors.is_empty());
+        report
+            .findings
+            .iter()
+            .map(|f| {
+                let path = f.path.strip_prefix(&root).unwrap().to_string_lossy();
+                format!("{} {}", path, f.rule)
+            })
+            .collect()
+    };
+    assert_eq!(
+        found(&Audit::new()),
+        vec![
+            "doc write-without-read",
+            "doc group-writable-in-private-dir",
+            "notes world-writable",
+            "notes exec-without-magic",
+            "script setid-executable",
+            "tmp world-writable-dir",
+        ]
+    );
+    let audit = Audit::new()
+        .disable(AuditRule::GroupWritableInPrivateDir)
+        .disable(AuditRule::from_id("exec-without-magic").unwrap());
+    assert_eq!(
+        found(&audit),
+        vec![
+            "doc write-without-read",
+            "notes world-writable",
+            "script setid-executable",
+            "tmp world-writable-dir",
+        ]
+    );
+    assert_eq!(
+        Audit::new().check_mode(FullMode::parse("drwxrwsr-T").unwrap(), None),
+        vec![AuditRule::ExtraWithoutExec]
+    );
+    for special in ["crw-rw-rw-", "brw-rw-rw-", "prw-rw-rw-", "srwxrwxrwx"] {
+        let full_mode = FullMode::parse(special).unwrap();
+        assert!(Audit::new()
+            .check_mode(full_mode, Some(Mode::from(0o755)))
+            .is_empty());
+    }
+}
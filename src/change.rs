@@ -0,0 +1,768 @@
+use {
+    crate::*,
+    std::{
+        fmt::{self, Display, Formatter, Write},
+        iter::Peekable,
+    },
+};
+
+/// All the bits a chmod change may touch: permissions and extra permissions
+const CHMOD_BITS: u32 = 0o7777;
+
+/// The operator of a chmod clause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeOp {
+    /// `+`: add the permissions
+    Add,
+    /// `-`: remove the permissions
+    Remove,
+    /// `=`: set exactly the permissions for the affected classes
+    Set,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    /// Permission bits given as letters (`rwxst`), with `X` stored
+    /// as a flag because it depends on the mode being changed
+    Bits { value: u32, cond_exec: bool },
+    /// Copy of the permissions of a class of the mode being changed (`g=u`)
+    Copy(Class),
+    /// Absolute octal mode (`755`), with the number of digits written
+    Octal { value: u32, digits: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Clause {
+    /// Affected bits, 0 when no "who" was given (the umask then applies)
+    who: u32,
+    op: ChangeOp,
+    operand: Operand,
+    /// Bits explicitly mentioned, which matters for the
+    /// setuid and setgid bits of directories
+    mentioned: u32,
+}
+
+/// A change to apply to modes, as described by a symbolic
+/// chmod(1) expression like `u+x,g-w,o=r`, `a+rX` or `g=u`,
+/// or by an octal mode like `755`.
+///
+/// ```
+/// use umask::*;
+///
+/// let change: ModeChange = "u+x,g-w,o=r".parse().unwrap();
+/// let mode = Mode::from(0o666);
+/// assert_eq!("rwxr--r--", change.apply_with_umask(mode, false, Mode::from(0o022)).to_string());
+/// ```
+///
+/// The rules are the ones of GNU chmod: when the "who" part of
+/// a clause is omitted, the bits set in the umask aren't changed,
+/// and the setuid and setgid bits of directories are kept unless
+/// explicitly mentioned (which, for an octal mode, means using 5
+/// digits or more, like `00755`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModeChange {
+    clauses: Vec<Clause>,
+}
+
+impl ModeChange {
+    /// Parse a chmod expression, made of comma separated clauses
+    /// like `ug+rw`, `o=`, `+t` or `g=u`, or being an octal mode
+    /// like `644`.
+    pub fn parse<T: AsRef<str>>(s: T) -> Result<Self, ParseError> {
+        let mut chars = s.as_ref().chars().enumerate().peekable();
+        let mut clauses = Vec::new();
+        // like GNU chmod, an octal mode can't be combined with clauses
+        if let Some((_, '0'..='9')) = chars.peek() {
+            clauses.push(Self::parse_octal_clause(&mut chars)?);
+            return match chars.next() {
+                None => Ok(Self { clauses }),
+                Some((pos, c)) => Err(ParseError::InvalidChar(c, pos)),
+            };
+        }
+        loop {
+            // who part
+            let mut who = 0;
+            while let Some(&(_, c)) = chars.peek() {
+                who |= match c {
+                    'u' => USER | SETUID,
+                    'g' => GROUP | SETGID,
+                    'o' => OTHERS | STICKY,
+                    'a' => CHMOD_BITS,
+                    _ => break,
+                };
+                chars.next();
+            }
+            // one or more actions
+            let mut actions = 0;
+            loop {
+                let op = match chars.peek() {
+                    Some((_, '+')) => ChangeOp::Add,
+                    Some((_, '-')) => ChangeOp::Remove,
+                    Some((_, '=')) => ChangeOp::Set,
+                    Some(&(pos, c)) if actions == 0 => {
+                        return Err(ParseError::InvalidChar(c, pos));
+                    }
+                    None if actions == 0 => return Err(ParseError::NotEnoughInput),
+                    _ => break,
+                };
+                chars.next();
+                actions += 1;
+                let copied = match chars.peek() {
+                    Some((_, 'u')) => Some(USER),
+                    Some((_, 'g')) => Some(GROUP),
+                    Some((_, 'o')) => Some(OTHERS),
+                    _ => None,
+                };
+                let operand = if let Some(class) = copied {
+                    chars.next();
+                    Operand::Copy(class)
+                } else {
+                    let mut value = 0;
+                    let mut cond_exec = false;
+                    while let Some(&(_, c)) = chars.peek() {
+                        match c {
+                            'r' => value |= READ,
+                            'w' => value |= WRITE,
+                            'x' => value |= EXEC,
+                            'X' => cond_exec = true,
+                            's' => value |= SETUID | SETGID,
+                            't' => value |= STICKY,
+                            _ => break,
+                        }
+                        chars.next();
+                    }
+                    Operand::Bits { value, cond_exec }
+                };
+                let mentioned = match operand {
+                    Operand::Bits { value, .. } | Operand::Copy(value) if who != 0 => who & value,
+                    Operand::Bits { value, .. } | Operand::Copy(value) => value,
+                    Operand::Octal { value, .. } => value,
+                };
+                clauses.push(Clause {
+                    who,
+                    op,
+                    operand,
+                    mentioned,
+                });
+            }
+            match chars.next() {
+                None => break,
+                Some((_, ',')) if chars.peek().is_some() => {}
+                Some((_, ',')) => return Err(ParseError::NotEnoughInput),
+                Some((pos, c)) => return Err(ParseError::InvalidChar(c, pos)),
+            }
+        }
+        Ok(Self { clauses })
+    }
+    fn parse_octal_clause(
+        chars: &mut Peekable<impl Iterator<Item = (usize, char)>>,
+    ) -> Result<Clause, ParseError> {
+        let mut value: u32 = 0;
+        let mut digits = 0;
+        while let Some(&(pos, c)) = chars.peek() {
+            let digit = match c {
+                '0'..='7' => c as u32 - '0' as u32,
+                '8' | '9' => return Err(ParseError::InvalidOctalDigit(c, pos)),
+                _ => break,
+            };
+            value = value * 8 + digit;
+            if value > CHMOD_BITS {
+                return Err(ParseError::OctalOutOfRange(format!("{:o}", value)));
+            }
+            digits += 1;
+            chars.next();
+        }
+        // like GNU chmod, the setuid and setgid bits of directories
+        // are only cleared by an octal mode when there are 5 digits
+        let mentioned = if digits < 5 {
+            (value & (SETUID | SETGID)) | STICKY | ALL
+        } else {
+            CHMOD_BITS
+        };
+        Ok(Clause {
+            who: CHMOD_BITS,
+            op: ChangeOp::Set,
+            operand: Operand::Octal { value, digits },
+            mentioned,
+        })
+    }
+    /// Return the change removing then adding exactly the bits
+    /// which differ between the two modes, with explicit "who" so
+    /// that neither the umask nor the file type matter.
+    ///
+    /// ```
+    /// use umask::*;
+    ///
+    /// let change = ModeChange::between(Mode::from(0o4775), Mode::from(0o750));
+    /// assert_eq!(change.to_string(), "u-s,g-w,o-rx");
+    /// assert_eq!(change.apply_to_dir(Mode::from(0o4775)), Mode::from(0o750));
+    /// ```
+    pub fn between(from: Mode, to: Mode) -> Self {
+        let from = u32::from(from) & CHMOD_BITS;
+        let to = u32::from(to) & CHMOD_BITS;
+        let mut clauses = Vec::new();
+        for (op, bits) in [(ChangeOp::Remove, from & !to), (ChangeOp::Add, to & !from)] {
+            // classes with the same changed bits share a clause
+            let mut groups: Vec<(u32, u32)> = Vec::new();
+            for who in [USER | SETUID, GROUP | SETGID, OTHERS | STICKY] {
+                let class_bits = bits & who;
+                if class_bits == 0 {
+                    continue;
+                }
+                let value = letters(class_bits);
+                match groups.iter_mut().find(|(_, v)| *v == value) {
+                    Some((group_who, _)) => *group_who |= who,
+                    None => groups.push((who, value)),
+                }
+            }
+            for (who, value) in groups {
+                clauses.push(Clause::bits(who, op, value));
+            }
+        }
+        Self { clauses }
+    }
+    /// Return the shortest change turning `from` into `to`, whatever
+    /// the file type and the umask, choosing between an octal mode,
+    /// relative clauses (`g+w,o-rwx`) and a clause setting all
+    /// classes followed by adjustments (`a=r,u+w`).
+    ///
+    /// When several changes have the same length, symbolic ones
+    /// are preferred to octal ones.
+    ///
+    /// Return `None` when both modes are the same, as there's no
+    /// empty chmod expression.
+    ///
+    /// ```
+    /// use umask::*;
+    ///
+    /// let shortest = |from, to| {
+    ///     ModeChange::shortest_between(Mode::from(from), Mode::from(to))
+    ///         .map(|change| change.to_string())
+    /// };
+    /// assert_eq!(shortest(0o644, 0o644), None);
+    /// assert_eq!(shortest(0o644, 0o664).unwrap(), "g+w");
+    /// assert_eq!(shortest(0o777, 0o640).unwrap(), "640");
+    /// assert_eq!(shortest(0o4755, 0o755).unwrap(), "u-s");
+    /// assert_eq!(shortest(0o2775, 0o755).unwrap(), "g-ws");
+    /// ```
+    pub fn shortest_between(from: Mode, to: Mode) -> Option<Self> {
+        let from = u32::from(from) & CHMOD_BITS;
+        let to = u32::from(to) & CHMOD_BITS;
+        if from == to {
+            return None;
+        }
+        let mut candidates = vec![Self::between(Mode::from(from), Mode::from(to))];
+        candidates.extend(Self::symbolic_candidates(Some(from), to));
+        // the setuid and setgid bits of directories are only
+        // cleared by an octal mode of 5 digits
+        let digits = if from & !to & (SETUID | SETGID) != 0 {
+            5
+        } else if to & EXTRA != 0 {
+            4
+        } else {
+            3
+        };
+        candidates.push(Self {
+            clauses: vec![Clause {
+                who: CHMOD_BITS,
+                op: ChangeOp::Set,
+                operand: Operand::Octal { value: to, digits },
+                mentioned: if digits < 5 {
+                    (to & (SETUID | SETGID)) | STICKY | ALL
+                } else {
+                    CHMOD_BITS
+                },
+            }],
+        });
+        Some(Self::shortest_of(candidates, &[from], &[false, true], to))
+    }
+    /// Return the shortest symbolic change giving a file the mode,
+    /// whatever its current mode, e.g. `a=r,u+w` for `rw-r--r--`.
+    ///
+    /// As with all symbolic changes, the setuid and setgid bits
+    /// of directories are kept when not mentioned.
+    ///
+    /// ```
+    /// use umask::*;
+    ///
+    /// let shortest = |mode| ModeChange::shortest_symbolic(Mode::from(mode)).to_string();
+    /// assert_eq!(shortest(0o644), "a=r,u+w");
+    /// assert_eq!(shortest(0o750), "a=rx,u+w,o=");
+    /// assert_eq!(shortest(0o1777), "a=rwxt");
+    /// ```
+    pub fn shortest_symbolic(mode: Mode) -> Self {
+        let to = u32::from(mode) & CHMOD_BITS;
+        let candidates = Self::symbolic_candidates(None, to);
+        Self::shortest_of(candidates, &[0, CHMOD_BITS], &[false], to)
+    }
+    /// Return symbolic changes leading to `to`, made of an optional
+    /// `a=` clause then, for every class, either nothing, a `=`
+    /// clause or relative clauses, classes with the same clauses
+    /// being grouped.
+    ///
+    /// When `from` is unknown, classes must be set by a `=` clause.
+    /// The changes aren't all valid for directories.
+    fn symbolic_candidates(from: Option<u32>, to: u32) -> Vec<Self> {
+        const CLASSES: [u32; 3] = [USER | SETUID, GROUP | SETGID, OTHERS | STICKY];
+        let mut candidates = Vec::new();
+        // the a= clauses: any rwx letters, with or without s and t
+        let bases = std::iter::once(None).chain((0..0o40).map(|n: u32| {
+            let mut base = (n & 0o7) * 0o111;
+            if n & 0o10 != 0 {
+                base |= SETUID | SETGID;
+            }
+            if n & 0o20 != 0 {
+                base |= STICKY;
+            }
+            Some(base)
+        }));
+        for base in bases {
+            let state = base.or(from);
+            let options: Vec<Vec<Vec<(ChangeOp, u32)>>> = CLASSES
+                .iter()
+                .map(|&who| {
+                    let target = to & who;
+                    let mut options = vec![vec![(ChangeOp::Set, letters(target))]];
+                    if let Some(state) = state {
+                        let current = state & who;
+                        if current == target {
+                            options.push(Vec::new());
+                        }
+                        let mut relative = Vec::new();
+                        if current & !target != 0 {
+                            relative.push((ChangeOp::Remove, letters(current & !target)));
+                        }
+                        if target & !current != 0 {
+                            relative.push((ChangeOp::Add, letters(target & !current)));
+                        }
+                        if !relative.is_empty() {
+                            options.push(relative);
+                        }
+                    }
+                    options
+                })
+                .collect();
+            for u in &options[0] {
+                for g in &options[1] {
+                    for o in &options[2] {
+                        let mut clauses = Vec::new();
+                        if let Some(base) = base {
+                            clauses.push(Clause::bits(CHMOD_BITS, ChangeOp::Set, base));
+                        }
+                        // classes with the same clauses share them
+                        let mut groups: Vec<(u32, &Vec<(ChangeOp, u32)>)> = Vec::new();
+                        for (&who, ops) in CLASSES.iter().zip([u, g, o]) {
+                            match groups.iter_mut().find(|(_, o)| *o == ops) {
+                                Some((group_who, _)) => *group_who |= who,
+                                None => groups.push((who, ops)),
+                            }
+                        }
+                        for (who, ops) in groups {
+                            for &(op, value) in ops {
+                                clauses.push(Clause::bits(who, op, value));
+                            }
+                        }
+                        candidates.push(Self { clauses });
+                    }
+                }
+            }
+        }
+        candidates
+    }
+    /// Return the shortest change giving the `to` mode from
+    /// all the starting modes, for all the file types
+    fn shortest_of(candidates: Vec<Self>, starts: &[u32], dir_flags: &[bool], to: u32) -> Self {
+        candidates
+            .into_iter()
+            .filter(|change| {
+                starts.iter().all(|&start| {
+                    dir_flags.iter().all(|&is_dir| {
+                        let mode = change.apply_with_umask(Mode::from(start), is_dir, Mode::new());
+                        u32::from(mode) == to
+                    })
+                })
+            })
+            .min_by_key(|change| change.to_string().len())
+            .unwrap_or_default()
+    }
+    /// Tell whether the change has no clause
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+    /// Return a change applying this one, then the other one.
+    ///
+    /// (the default `ModeChange` is empty and changes nothing)
+    pub fn then(mut self, other: &ModeChange) -> Self {
+        self.clauses.extend_from_slice(&other.clauses);
+        self
+    }
+    /// Apply the change to the mode of a file which isn't a directory,
+    /// using the umask of the current process for clauses without "who".
+    ///
+    /// Each call reads the umask with [`Umask::current`], which reads
+    /// `/proc/self/status` or, elsewhere, briefly changes the umask of the
+    /// process. To apply changes to many modes, read the umask once and
+    /// call [`ModeChange::apply_with_umask`].
+    pub fn apply(&self, mode: Mode) -> Mode {
+        self.apply_with_umask(mode, false, Umask::current().mode())
+    }
+    /// Apply the change to the mode of a directory, using the
+    /// umask of the current process for clauses without "who".
+    ///
+    /// Like [`ModeChange::apply`], each call reads the umask.
+    pub fn apply_to_dir(&self, mode: Mode) -> Mode {
+        self.apply_with_umask(mode, true, Umask::current().mode())
+    }
+    /// Apply the change to a mode, with an explicit umask.
+    ///
+    /// This is pure bit arithmetic: when applying a change to many
+    /// modes, get the umask once, e.g. with `Umask::current().mode()`.
+    ///
+    /// `is_dir` matters for `X` (which gives execution permission
+    /// to directories) and for the preservation of the setuid and
+    /// setgid bits of directories.
+    ///
+    /// Bits out of the permission and extra permission ranges
+    /// (e.g. file type bits) are kept unchanged.
+    pub fn apply_with_umask(&self, mode: Mode, is_dir: bool, umask: Mode) -> Mode {
+        let umask = u32::from(umask);
+        let mut new_mode = u32::from(mode);
+        for clause in &self.clauses {
+            let affected = clause.who;
+            let mut value = match clause.operand {
+                Operand::Octal { value, .. } => value,
+                Operand::Bits { value, cond_exec } => {
+                    if cond_exec && (is_dir || new_mode & EXEC != 0) {
+                        value | EXEC
+                    } else {
+                        value
+                    }
+                }
+                Operand::Copy(class) => {
+                    let copied = new_mode & class;
+                    let mut value = 0;
+                    for perm in [READ, WRITE, EXEC] {
+                        if copied & perm != 0 {
+                            value |= perm;
+                        }
+                    }
+                    value
+                }
+            };
+            let omitted = if is_dir {
+                (SETUID | SETGID) & !clause.mentioned
+            } else {
+                0
+            };
+            value &= (if affected != 0 { affected } else { !umask }) & !omitted & CHMOD_BITS;
+            match clause.op {
+                ChangeOp::Add => new_mode |= value,
+                ChangeOp::Remove => new_mode &= !value,
+                ChangeOp::Set => {
+                    let preserved = (if affected != 0 {
+                        !affected
+                    } else {
+                        !CHMOD_BITS
+                    }) | omitted;
+                    new_mode = (new_mode & preserved) | value;
+                }
+            }
+        }
+        Mode::from(new_mode)
+    }
+}
+
+impl Clause {
+    /// A clause with an explicit "who" and permission letters
+    fn bits(who: u32, op: ChangeOp, value: u32) -> Self {
+        Self {
+            who,
+            op,
+            operand: Operand::Bits {
+                value,
+                cond_exec: false,
+            },
+            mentioned: who & value,
+        }
+    }
+}
+
+/// Return the operand value of the permission letters
+/// (`rwxst`) needed to designate the given bits
+fn letters(bits: u32) -> u32 {
+    let mut value = 0;
+    for perm in [READ, WRITE, EXEC] {
+        if bits & perm != 0 {
+            value |= perm;
+        }
+    }
+    if bits & (SETUID | SETGID) != 0 {
+        value |= SETUID | SETGID;
+    }
+    if bits & STICKY != 0 {
+        value |= STICKY;
+    }
+    value
+}
+
+impl std::str::FromStr for ModeChange {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for ChangeOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_char(match self {
+            Self::Add => '+',
+            Self::Remove => '-',
+            Self::Set => '=',
+        })
+    }
+}
+
+impl Display for ModeChange {
+    /// Formats the change as a symbolic chmod expression
+    /// which can be parsed back, unless an octal change was
+    /// combined with other ones with [`ModeChange::then`]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut previous_who = None;
+        for (i, clause) in self.clauses.iter().enumerate() {
+            let operand = match clause.operand {
+                Operand::Octal { value, digits } => {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{:0width$o}", value, width = digits)?;
+                    previous_who = None;
+                    continue;
+                }
+                Operand::Bits { value, cond_exec } => {
+                    let mut letters = String::new();
+                    for (bits, letter) in [(READ, 'r'), (WRITE, 'w'), (EXEC, 'x')] {
+                        if value & bits != 0 {
+                            letters.push(letter);
+                        }
+                    }
+                    if cond_exec {
+                        letters.push('X');
+                    }
+                    if value & (SETUID | SETGID) != 0 {
+                        letters.push('s');
+                    }
+                    if value & STICKY != 0 {
+                        letters.push('t');
+                    }
+                    letters
+                }
+                Operand::Copy(class) => match class {
+                    USER => "u",
+                    GROUP => "g",
+                    _ => "o",
+                }
+                .to_string(),
+            };
+            if previous_who != Some(clause.who) {
+                if i > 0 {
+                    f.write_char(',')?;
+                }
+                if clause.who == CHMOD_BITS {
+                    f.write_char('a')?;
+                } else {
+                    if clause.who & USER != 0 {
+                        f.write_char('u')?;
+                    }
+                    if clause.who & GROUP != 0 {
+                        f.write_char('g')?;
+                    }
+                    if clause.who & OTHERS != 0 {
+                        f.write_char('o')?;
+                    }
+                }
+                previous_who = Some(clause.who);
+            }
+            write!(f, "{}{}", clause.op, operand)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_change_parse() {
+    for s in [
+        "u+x,g-w,o=r",
+        "a+rX",
+        "ug=rw,+t",
+        "g=u",
+        "u+s",
+        "u+r-w",
+        "o=",
+        "+x",
+        "755",
+        "00644",
+    ] {
+        let change = ModeChange::parse(s).unwrap();
+        assert_eq!(change.to_string(), s);
+    }
+    assert!(matches!(
+        ModeChange::parse(""),
+        Err(ParseError::NotEnoughInput)
+    ));
+    assert!(matches!(
+        ModeChange::parse("u"),
+        Err(ParseError::NotEnoughInput)
+    ));
+    assert!(matches!(
+        ModeChange::parse("u+x,"),
+        Err(ParseError::NotEnoughInput)
+    ));
+    assert!(matches!(
+        ModeChange::parse("u+x,k=r"),
+        Err(ParseError::InvalidChar('k', 4))
+    ));
+    assert!(matches!(
+        ModeChange::parse("758"),
+        Err(ParseError::InvalidOctalDigit('8', 2))
+    ));
+    assert!(matches!(
+        ModeChange::parse("17777"),
+        Err(ParseError::OctalOutOfRange(_))
+    ));
+    assert!(matches!(
+        ModeChange::parse("u+x,644,g-w"),
+        Err(ParseError::InvalidChar('6', 4))
+    ));
+    assert!(matches!(
+        ModeChange::parse("644,u+x"),
+        Err(ParseError::InvalidChar(',', 3))
+    ));
+    assert!(matches!(
+        ModeChange::parse("g=uw"),
+        Err(ParseError::InvalidChar('w', 3))
+    ));
+}
+
+#[test]
+fn test_change_apply() {
+    let umask = Mode::from(0o022);
+    let apply = |s: &str, mode: u32, is_dir: bool| -> u32 {
+        let change = ModeChange::parse(s).unwrap();
+        change
+            .apply_with_umask(Mode::from(mode), is_dir, umask)
+            .into()
+    };
+    assert_eq!(apply("u+x,g-w,o=r", 0o666, false), 0o744);
+    assert_eq!(apply("a+rX", 0o600, false), 0o644);
+    assert_eq!(apply("a+rX", 0o700, false), 0o755);
+    assert_eq!(apply("a+rX", 0o600, true), 0o755);
+    assert_eq!(apply("ug=rw,+t", 0o755, false), 0o1665);
+    assert_eq!(apply("g=u", 0o640, false), 0o660);
+    assert_eq!(apply("u+s", 0o755, false), 0o4755);
+    assert_eq!(apply("g-s", 0o2755, false), 0o755);
+    // without who, the umask is respected
+    assert_eq!(apply("+w", 0o444, false), 0o644);
+    assert_eq!(apply("=rwx", 0o4000, false), 0o755);
+    // setgid of directories is kept unless mentioned
+    assert_eq!(apply("a=rx", 0o2775, true), 0o2555);
+    assert_eq!(apply("a=rx", 0o2775, false), 0o555);
+    // octal modes keep the setuid and setgid bits of directories
+    // unless 5 digits are used
+    assert_eq!(apply("755", 0o6777, false), 0o755);
+    assert_eq!(apply("755", 0o3777, true), 0o2755);
+    assert_eq!(apply("00755", 0o3777, true), 0o755);
+    assert_eq!(apply("4755", 0o2777, true), 0o6755);
+    // file type bits are preserved
+    assert_eq!(apply("a=r", 0o100644, false), 0o100444);
+}
+
+#[test]
+fn test_change_between() {
+    let modes = [
+        0o000, 0o644, 0o755, 0o4755, 0o2770, 0o1777, 0o7000, 0o640, 0o777,
+    ];
+    for &from in &modes {
+        for &to in &modes {
+            let change = ModeChange::between(Mode::from(from), Mode::from(to));
+            let parsed = ModeChange::parse(change.to_string());
+            let parsed = if change.is_empty() {
+                ModeChange::default()
+            } else {
+                parsed.unwrap()
+            };
+            for is_dir in [false, true] {
+                for change in [&change, &parsed] {
+                    let result =
+                        change.apply_with_umask(Mode::from(from), is_dir, Mode::from(0o077));
+                    assert_eq!(
+                        u32::from(result),
+                        to,
+                        "{:o} -> {:o} with {}",
+                        from,
+                        to,
+                        change
+                    );
+                }
+            }
+        }
+    }
+    let change = ModeChange::between(Mode::from(0o644), Mode::from(0o755));
+    assert_eq!(change.to_string(), "a+x");
+    let change = ModeChange::between(Mode::from(0o2770), Mode::from(0o1755));
+    assert_eq!(change.to_string(), "g-ws,o+rxt");
+}
+
+#[test]
+fn test_change_shortest() {
+    let modes = [
+        0o000, 0o644, 0o755, 0o4755, 0o2770, 0o1777, 0o7000, 0o640, 0o777, 0o600, 0o2775,
+    ];
+    let umask = Mode::from(0o077);
+    for &to in &modes {
+        let symbolic = ModeChange::shortest_symbolic(Mode::from(to));
+        assert_eq!(ModeChange::parse(symbolic.to_string()).unwrap(), symbolic);
+        for from in [0, 0o7777] {
+            let result = symbolic.apply_with_umask(Mode::from(from), false, umask);
+            assert_eq!(u32::from(result), to, "{:o} with {}", from, symbolic);
+        }
+        for &from in &modes {
+            let change = match ModeChange::shortest_between(Mode::from(from), Mode::from(to)) {
+                Some(change) => change,
+                None => {
+                    assert_eq!(from, to);
+                    continue;
+                }
+            };
+            let between = ModeChange::between(Mode::from(from), Mode::from(to));
+            assert!(change.to_string().len() <= between.to_string().len());
+            for is_dir in [false, true] {
+                let result = change.apply_with_umask(Mode::from(from), is_dir, umask);
+                assert_eq!(
+                    u32::from(result),
+                    to,
+                    "{:o} -> {:o} with {}",
+                    from,
+                    to,
+                    change
+                );
+            }
+        }
+    }
+    assert_eq!(
+        ModeChange::shortest_between(Mode::from(0o644), Mode::from(0o644)),
+        None
+    );
+    assert_eq!(
+        ModeChange::shortest_between(Mode::from(0o100644), Mode::from(0o644)),
+        None
+    );
+    let shortest = |from, to| {
+        ModeChange::shortest_between(Mode::from(from), Mode::from(to))
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(shortest(0o777, 0o700), "go=");
+    assert_eq!(shortest(0o2755, 0o644), "00644");
+    assert_eq!(shortest(0o600, 0o1644), "1644");
+    assert_eq!(shortest(0o600, 0o1600), "o+t");
+}
@@ -0,0 +1,166 @@
+use {
+    crate::*,
+    std::{
+        collections::BTreeMap,
+        fmt::{self, Display, Formatter},
+        path::{Path, PathBuf},
+    },
+};
+
+/// An entry found in both trees with different file types or modes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeDifference {
+    /// The path relative to the roots, `.` for the roots themselves
+    pub path: PathBuf,
+    pub left: FullMode,
+    pub right: FullMode,
+    /// The bits set on one side only, going from left to right
+    pub changes: Vec<BitChange>,
+}
+
+impl ModeDifference {
+    pub fn is_type_change(&self) -> bool {
+        self.left.file_type != self.right.file_type
+    }
+}
+
+impl Display for ModeDifference {
+    /// Formats the difference as e.g.
+    /// "shared: drwxrwsr-x -> drwxr-xr-t: group lost write, setgid removed, sticky added"
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path.display(),
+            self.left,
+            self.right
+        )?;
+        if self.is_type_change() {
+            return f.write_str(": file type differs");
+        }
+        for (idx, change) in self.changes.iter().enumerate() {
+            f.write_str(if idx == 0 { ": " } else { ", " })?;
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of a [`compare_trees`]
+#[derive(Debug, Default)]
+pub struct TreeComparison {
+    pub differences: Vec<ModeDifference>,
+    /// Relative paths of the entries of the left tree only
+    pub only_left: Vec<PathBuf>,
+    /// Relative paths of the entries of the right tree only
+    pub only_right: Vec<PathBuf>,
+    /// Errors met while reading the trees, making the comparison incomplete
+    pub errors: Vec<TreeError>,
+}
+
+impl TreeComparison {
+    /// Tell whether the trees have the same entries with the same modes
+    pub fn is_same(&self) -> bool {
+        self.differences.is_empty()
+            && self.only_left.is_empty()
+            && self.only_right.is_empty()
+            && self.errors.is_empty()
+    }
+}
+
+impl Display for TreeComparison {
+    /// Formats the comparison like `diff -r`, with one line per entry
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for difference in &self.differences {
+            writeln!(f, "{}", difference)?;
+        }
+        for path in &self.only_left {
+            writeln!(f, "only in left: {}", path.display())?;
+        }
+        for path in &self.only_right {
+            writeln!(f, "only in right: {}", path.display())?;
+        }
+        for error in &self.errors {
+            writeln!(f, "error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the modes of the entries of two trees, matched by their
+/// path relative to the roots. Symbolic links aren't followed.
+///
+/// Only the modes and file types are compared, not the owners nor
+/// the content.
+///
+/// ```no_run
+/// use umask::*;
+///
+/// let comparison = compare_trees("/srv/staging", "/srv/production");
+/// if !comparison.is_same() {
+///     eprint!("{}", comparison);
+/// }
+/// ```
+pub fn compare_trees<L: AsRef<Path>, R: AsRef<Path>>(left: L, right: R) -> TreeComparison {
+    let mut comparison = TreeComparison::default();
+    let mut read = |root: &Path| -> BTreeMap<PathBuf, FullMode> {
+        let mut entries = BTreeMap::new();
+        for entry in TreeWalker::new(root) {
+            match entry {
+                Ok(entry) => {
+                    let entry = ManifestEntry::new(root, &entry);
+                    entries.insert(entry.path, entry.full_mode);
+                }
+                Err(e) => comparison.errors.push(e),
+            }
+        }
+        entries
+    };
+    let mut left = read(left.as_ref());
+    let right = read(right.as_ref());
+    for (path, right) in right {
+        let left = match left.remove(&path) {
+            Some(left) => left,
+            None => {
+                comparison.only_right.push(path);
+                continue;
+            }
+        };
+        if left != right {
+            comparison.differences.push(ModeDifference {
+                path,
+                left,
+                right,
+                changes: BitChange::list(left.mode, right.mode),
+            });
+        }
+    }
+    comparison.only_left = left.into_keys().collect();
+    comparison
+}
+
+#[test]
+fn test_compare_trees() {
+    use std::fs;
+    let base = TestDir::new("compare");
+    let (left, right) = (base.join("left"), base.join("right"));
+    for root in [&left, &right] {
+        fs::create_dir_all(root.join("shared")).unwrap();
+        fs::write(root.join("shared/notes"), "").unwrap();
+        fs::write(root.join("run"), "").unwrap();
+        Mode::from(0o2775).apply_to(root.join("shared")).unwrap();
+        Mode::from(0o755).apply_to(root.join("run")).unwrap();
+    }
+    fs::write(left.join("old"), "").unwrap();
+    fs::create_dir(right.join("new")).unwrap();
+    Mode::from(0o3755).apply_to(right.join("shared")).unwrap();
+    let comparison = compare_trees(&left, &right);
+    assert!(comparison.errors.is_empty());
+    assert_eq!(comparison.only_left, vec![PathBuf::from("old")]);
+    assert_eq!(comparison.only_right, vec![PathBuf::from("new")]);
+    assert_eq!(comparison.differences.len(), 1);
+    assert_eq!(
+        comparison.differences[0].to_string(),
+        "shared: drwxrwsr-x -> drwxr-sr-t: group lost write, sticky added"
+    );
+}
@@ -0,0 +1,148 @@
+use crate::*;
+
+/// The default ACL of a directory, as far as it matters for
+/// the mode of new entries
+///
+/// When a directory has a default ACL, the umask isn't used for
+/// the entries created in it: the default ACL is used instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DefaultAcl {
+    /// The `user::`, `group::` and `other::` entries, as a mode
+    pub mode: Mode,
+    /// The `mask::` entry, in the group class, if any
+    pub mask: Option<Mode>,
+}
+
+impl DefaultAcl {
+    /// Return the mode which limits the requested one,
+    /// the mask replacing the `group::` entry when present
+    pub fn limit(self) -> Mode {
+        match self.mask {
+            Some(mask) => self.mode.without(Mode::from(GROUP)) | (mask & Mode::from(GROUP)),
+            None => self.mode,
+        }
+    }
+}
+
+/// The predicted owner and mode of a new file or directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Creation {
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: Mode,
+    /// Whether the group comes from the setgid parent directory
+    /// instead of the creating process
+    pub group_inherited: bool,
+    /// Whether the default ACL of the parent was used instead of the umask
+    pub acl_applied: bool,
+}
+
+/// Predict the owner, group and mode of an entry created by open(2)
+/// (with `O_CREAT`) or mkdir(2) in a directory.
+///
+/// - the owner is the uid of the creator
+/// - the group is the one of the directory if it has the setgid bit,
+///   the primary group of the creator otherwise
+/// - a new directory in a setgid directory is setgid too
+/// - the default ACL of the directory, if any, replaces the umask
+/// - the setgid bit requested for a file with group execution is dropped
+///   when the creator isn't in the group of the new file (and has no
+///   CAP_FSETID, which isn't modeled). Like Linux since 6.0, this is decided
+///   on the requested mode, before the umask is applied. Setgid without group
+///   execution, the mandatory locking marker, is kept.
+///
+/// ```
+/// use umask::*;
+///
+/// // a shared directory, setgid with group 100
+/// let dir = FileStat::new(FileType::Directory, Mode::from(0o2775), 0, 100);
+/// let creator = Identity::new(1000, 1000);
+/// let umask = Umask::from(Mode::from(0o022));
+/// let file = predict_creation(&dir, None, &creator, umask, FileType::Regular, Mode::from(0o666));
+/// assert_eq!((file.uid, file.gid, file.mode), (1000, 100, Mode::from(0o644)));
+/// let sub = predict_creation(&dir, None, &creator, umask, FileType::Directory, Mode::from(0o777));
+/// assert_eq!((sub.gid, sub.mode), (100, Mode::from(0o2755)));
+/// ```
+pub fn predict_creation(
+    dir: &FileStat,
+    default_acl: Option<&DefaultAcl>,
+    creator: &Identity,
+    umask: Umask,
+    file_type: FileType,
+    requested: Mode,
+) -> Creation {
+    let is_dir = file_type == FileType::Directory;
+    let group_inherited = dir.mode.has_extra(SETGID);
+    let gid = if group_inherited {
+        dir.gid
+    } else {
+        creator.gid
+    };
+    let mut mode = match default_acl {
+        Some(acl) => {
+            let extra_mask = if is_dir { STICKY } else { EXTRA };
+            requested.intersection(acl.limit().with_extra(extra_mask))
+        }
+        None if is_dir => umask.dir_creation_mode(requested),
+        None => umask.file_creation_mode(requested),
+    };
+    if is_dir && group_inherited {
+        mode = mode.with_extra(SETGID);
+    }
+    if !is_dir && requested.has_extra(SETGID) && requested.has(GROUP_EXEC) && !creator.in_group(gid)
+    {
+        mode = mode.without_extra(SETGID);
+    }
+    Creation {
+        uid: creator.uid,
+        gid,
+        mode,
+        group_inherited,
+        acl_applied: default_acl.is_some(),
+    }
+}
+
+#[test]
+fn test_predict_creation() {
+    let plain_dir = FileStat::new(FileType::Directory, Mode::from(0o755), 0, 0);
+    let shared_dir = FileStat::new(FileType::Directory, Mode::from(0o2770), 0, 100);
+    let creator = Identity::new(1000, 1000);
+    let umask = Umask::from(Mode::from(0o077));
+    let predict = |dir, acl, file_type, requested| {
+        predict_creation(dir, acl, &creator, umask, file_type, Mode::from(requested))
+    };
+    let file = predict(&plain_dir, None, FileType::Regular, 0o666);
+    assert_eq!((file.gid, file.mode), (1000, Mode::from(0o600)));
+    assert!(!file.group_inherited);
+    let file = predict(&shared_dir, None, FileType::Regular, 0o666);
+    assert_eq!((file.gid, file.mode), (100, Mode::from(0o600)));
+    assert!(file.group_inherited);
+    // the creator isn't in group 100, so setgid is dropped
+    let file = predict(&shared_dir, None, FileType::Regular, 0o2755);
+    assert_eq!(file.mode, Mode::from(0o700));
+    // even when the umask removes the group execution
+    let file = predict(&shared_dir, None, FileType::Regular, 0o2775);
+    assert_eq!(file.mode, Mode::from(0o700));
+    // but kept without group execution (mandatory locking)
+    let file = predict_creation(
+        &shared_dir,
+        None,
+        &creator,
+        Umask::from(Mode::from(0o022)),
+        FileType::Regular,
+        Mode::from(0o2644),
+    );
+    assert_eq!(file.mode, Mode::from(0o2644));
+    let sub_dir = predict(&shared_dir, None, FileType::Directory, 0o777);
+    assert_eq!((sub_dir.gid, sub_dir.mode), (100, Mode::from(0o2700)));
+    // with a default ACL, the umask is ignored
+    let acl = DefaultAcl {
+        mode: Mode::from(0o770),
+        mask: Some(Mode::from(0o050)),
+    };
+    let file = predict(&shared_dir, Some(&acl), FileType::Regular, 0o666);
+    assert_eq!(file.mode, Mode::from(0o640));
+    assert!(file.acl_applied);
+    let sub_dir = predict(&shared_dir, Some(&acl), FileType::Directory, 0o777);
+    assert_eq!(sub_dir.mode, Mode::from(0o2750));
+}
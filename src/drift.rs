@@ -0,0 +1,434 @@
+use {
+    crate::*,
+    std::{
+        collections::BTreeMap,
+        fmt::{self, Display, Formatter},
+        path::{Path, PathBuf},
+    },
+};
+
+/// A difference between a [`Manifest`] and the current state of the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drift {
+    /// The mode changed (the file type is the same)
+    ModeChanged { old: Mode, new: Mode },
+    /// The owner or the group changed
+    OwnerChanged {
+        old_uid: u32,
+        old_gid: u32,
+        new_uid: u32,
+        new_gid: u32,
+    },
+    /// The entry was replaced with one of another file type
+    TypeChanged { old: FullMode, new: FullMode },
+    /// The entry isn't in the manifest
+    Added {
+        full_mode: FullMode,
+        uid: u32,
+        gid: u32,
+    },
+    /// The entry of the manifest isn't in the tree anymore
+    Removed {
+        full_mode: FullMode,
+        uid: u32,
+        gid: u32,
+    },
+}
+
+impl Drift {
+    /// The identifier of the kind of drift, e.g. `"mode_changed"`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::ModeChanged { .. } => "mode_changed",
+            Self::OwnerChanged { .. } => "owner_changed",
+            Self::TypeChanged { .. } => "type_changed",
+            Self::Added { .. } => "added",
+            Self::Removed { .. } => "removed",
+        }
+    }
+    /// Tell whether the entry may now be accessed by someone the manifest
+    /// didn't allow: a mode which gained bits, an entry replaced by one
+    /// with a wider mode, an added entry with a non empty mode, or a new
+    /// owner or group, which gets the access of the owner or group class.
+    ///
+    /// The modes of symbolic links, which aren't used, never count.
+    pub fn is_relaxation(&self) -> bool {
+        match self {
+            Self::ModeChanged { old, new } => new.without(*old) != Mode::new(),
+            Self::TypeChanged { old, new } => {
+                new.file_type != FileType::Symlink && new.mode.without(old.mode) != Mode::new()
+            }
+            Self::Added { full_mode, .. } => {
+                full_mode.file_type != FileType::Symlink && full_mode.mode != Mode::new()
+            }
+            Self::OwnerChanged { .. } => true,
+            Self::Removed { .. } => false,
+        }
+    }
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ModeChanged { old, new } => write!(
+                f,
+                "mode changed from {:04o} ({}) to {:04o} ({})",
+                u32::from(old),
+                old,
+                u32::from(new),
+                new
+            ),
+            Self::OwnerChanged {
+                old_uid,
+                old_gid,
+                new_uid,
+                new_gid,
+            } => write!(
+                f,
+                "owner changed from {}:{} to {}:{}",
+                old_uid, old_gid, new_uid, new_gid
+            ),
+            Self::TypeChanged { old, new } => write!(f, "type changed from {} to {}", old, new),
+            Self::Added {
+                full_mode,
+                uid,
+                gid,
+            } => write!(f, "added ({} {}:{})", full_mode, uid, gid),
+            Self::Removed {
+                full_mode,
+                uid,
+                gid,
+            } => write!(f, "removed ({} {}:{})", full_mode, uid, gid),
+        }
+    }
+}
+
+/// A drift of an entry, whose path is relative to the root of the tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriftEntry {
+    pub path: PathBuf,
+    pub drift: Drift,
+}
+
+impl Display for DriftEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.drift)
+    }
+}
+
+/// The differences between a manifest and the current tree
+#[derive(Debug, Default)]
+pub struct DriftReport {
+    pub entries: Vec<DriftEntry>,
+    /// Errors met while reading the tree, making the report incomplete
+    pub errors: Vec<TreeError>,
+}
+
+impl DriftReport {
+    /// Tell whether the tree is in the state recorded in the manifest
+    pub fn is_clean(&self) -> bool {
+        self.entries.is_empty() && self.errors.is_empty()
+    }
+    /// Return the entries granting access the manifest didn't,
+    /// see [`Drift::is_relaxation`]
+    pub fn relaxations(&self) -> impl Iterator<Item = &DriftEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.drift.is_relaxation())
+    }
+    /// Return the report as a JSON document, e.g.
+    ///
+    /// ```json
+    /// {
+    ///   "drifts": [
+    ///     {
+    ///       "path": "data/secret",
+    ///       "kind": "mode_changed",
+    ///       "old_mode": "0640",
+    ///       "new_mode": "0644"
+    ///     }
+    ///   ],
+    ///   "errors": []
+    /// }
+    /// ```
+    ///
+    /// Modes are 4 digit octal strings and full modes are in the `ls -l`
+    /// notation. Paths which aren't valid UTF-8 are converted lossily.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        use serde_json::{json, Value};
+        let octal = |mode: &Mode| format!("{:04o}", u32::from(mode));
+        let drifts: Vec<Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut value = match &entry.drift {
+                    Drift::ModeChanged { old, new } => json!({
+                        "old_mode": octal(old),
+                        "new_mode": octal(new),
+                    }),
+                    Drift::OwnerChanged {
+                        old_uid,
+                        old_gid,
+                        new_uid,
+                        new_gid,
+                    } => json!({
+                        "old_uid": old_uid,
+                        "old_gid": old_gid,
+                        "new_uid": new_uid,
+                        "new_gid": new_gid,
+                    }),
+                    Drift::TypeChanged { old, new } => json!({
+                        "old_full_mode": old.to_string(),
+                        "new_full_mode": new.to_string(),
+                    }),
+                    Drift::Added {
+                        full_mode,
+                        uid,
+                        gid,
+                    }
+                    | Drift::Removed {
+                        full_mode,
+                        uid,
+                        gid,
+                    } => json!({
+                        "full_mode": full_mode.to_string(),
+                        "uid": uid,
+                        "gid": gid,
+                    }),
+                };
+                value["path"] = json!(entry.path.to_string_lossy());
+                value["kind"] = json!(entry.drift.kind());
+                value
+            })
+            .collect();
+        let errors: Vec<String> = self.errors.iter().map(|e| e.to_string()).collect();
+        let report = json!({
+            "drifts": drifts,
+            "errors": errors,
+        });
+        serde_json::to_string_pretty(&report).unwrap_or_default()
+    }
+}
+
+impl Display for DriftReport {
+    /// Formats the report with one line per drift, then one per error
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        for error in &self.errors {
+            writeln!(f, "error: {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Manifest {
+    /// Return the differences from this manifest to another one,
+    /// ordered by path
+    pub fn diff(&self, current: &Manifest) -> Vec<DriftEntry> {
+        let by_path = |manifest: &Manifest| -> BTreeMap<PathBuf, ManifestEntry> {
+            manifest
+                .entries
+                .iter()
+                .map(|entry| (entry.path.clone(), entry.clone()))
+                .collect()
+        };
+        let mut old = by_path(self);
+        let new = by_path(current);
+        let mut drifts = Vec::new();
+        for (path, new) in new {
+            let old = match old.remove(&path) {
+                Some(old) => old,
+                None => {
+                    drifts.push(DriftEntry {
+                        path,
+                        drift: Drift::Added {
+                            full_mode: new.full_mode,
+                            uid: new.uid,
+                            gid: new.gid,
+                        },
+                    });
+                    continue;
+                }
+            };
+            if old.full_mode.file_type != new.full_mode.file_type {
+                drifts.push(DriftEntry {
+                    path: path.clone(),
+                    drift: Drift::TypeChanged {
+                        old: old.full_mode,
+                        new: new.full_mode,
+                    },
+                });
+            } else if old.full_mode.mode != new.full_mode.mode {
+                drifts.push(DriftEntry {
+                    path: path.clone(),
+                    drift: Drift::ModeChanged {
+                        old: old.full_mode.mode,
+                        new: new.full_mode.mode,
+                    },
+                });
+            }
+            if (old.uid, old.gid) != (new.uid, new.gid) {
+                drifts.push(DriftEntry {
+                    path,
+                    drift: Drift::OwnerChanged {
+                        old_uid: old.uid,
+                        old_gid: old.gid,
+                        new_uid: new.uid,
+                        new_gid: new.gid,
+                    },
+                });
+            }
+        }
+        for (path, old) in old {
+            drifts.push(DriftEntry {
+                path,
+                drift: Drift::Removed {
+                    full_mode: old.full_mode,
+                    uid: old.uid,
+                    gid: old.gid,
+                },
+            });
+        }
+        drifts.sort_by(|a, b| a.path.cmp(&b.path));
+        drifts
+    }
+}
+
+/// Compare a manifest made by [`snapshot`] with the current state of the tree
+///
+/// ```no_run
+/// use umask::*;
+///
+/// let manifest: Manifest = std::fs::read_to_string("release.perms").unwrap().parse().unwrap();
+/// let report = detect_drift(&manifest, "/srv/app");
+/// if report.relaxations().next().is_some() {
+///     eprint!("{}", report);
+/// }
+/// ```
+pub fn detect_drift<P: AsRef<Path>>(manifest: &Manifest, root: P) -> DriftReport {
+    let root = root.as_ref();
+    let mut current = Manifest::default();
+    let mut report = DriftReport::default();
+    for entry in TreeWalker::new(root) {
+        match entry {
+            Ok(entry) => current.entries.push(ManifestEntry::new(root, &entry)),
+            Err(e) => report.errors.push(e),
+        }
+    }
+    report.entries = manifest.diff(&current);
+    report
+}
+
+#[test]
+fn test_detect_drift() {
+    use std::fs;
+    let root = TestDir::new("drift");
+    fs::create_dir_all(root.join("data")).unwrap();
+    fs::write(root.join("data/secret"), "").unwrap();
+    fs::write(root.join("data/old"), "").unwrap();
+    fs::write(root.join("swap"), "").unwrap();
+    Mode::from(0o640)
+        .apply_to(root.join("data/secret"))
+        .unwrap();
+    let manifest = snapshot(&root).manifest;
+    assert!(detect_drift(&manifest, &root).is_clean());
+
+    Mode::from(0o644)
+        .apply_to(root.join("data/secret"))
+        .unwrap();
+    fs::remove_file(root.join("data/old")).unwrap();
+    fs::write(root.join("data/new"), "").unwrap();
+    fs::remove_file(root.join("swap")).unwrap();
+    fs::create_dir(root.join("swap")).unwrap();
+    let report = detect_drift(&manifest, &root);
+    let kinds: Vec<String> = report
+        .entries
+        .iter()
+        .map(|entry| format!("{} {}", entry.path.display(), entry.drift.kind()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "data/new added",
+            "data/old removed",
+            "data/secret mode_changed",
+            "swap type_changed",
+        ]
+    );
+    let relaxed: Vec<String> = report
+        .relaxations()
+        .map(|entry| entry.path.display().to_string())
+        .collect();
+    assert_eq!(relaxed, vec!["data/new", "data/secret", "swap"]);
+    assert_eq!(
+        report.entries[2].to_string(),
+        "data/secret: mode changed from 0640 (rw-r-----) to 0644 (rw-r--r--)"
+    );
+    #[cfg(feature = "json")]
+    {
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        let secret = &json["drifts"][2];
+        assert_eq!(secret["path"], "data/secret");
+        assert_eq!(secret["kind"], "mode_changed");
+        assert_eq!(secret["old_mode"], "0640");
+        assert_eq!(secret["new_mode"], "0644");
+        assert_eq!(
+            json["drifts"][3]["new_full_mode"]
+                .as_str()
+                .unwrap()
+                .chars()
+                .next(),
+            Some('d')
+        );
+        assert_eq!(json["errors"], serde_json::json!([]));
+    }
+}
+
+#[test]
+fn test_manifest_diff_owner() {
+    let manifest = |uid| Manifest {
+        entries: vec![ManifestEntry {
+            path: "file".into(),
+            full_mode: FullMode::parse("-rw-r--r--").unwrap(),
+            uid,
+            gid: 100,
+        }],
+    };
+    let drifts = manifest(1000).diff(&manifest(0));
+    assert_eq!(drifts.len(), 1);
+    assert_eq!(
+        drifts[0].to_string(),
+        "file: owner changed from 1000:100 to 0:100"
+    );
+    // the new owner may read the file
+    assert!(drifts[0].drift.is_relaxation());
+}
+
+#[test]
+fn test_drift_relaxation() {
+    let full_mode = |s| FullMode::parse(s).unwrap();
+    let type_changed = |old, new| Drift::TypeChanged {
+        old: full_mode(old),
+        new: full_mode(new),
+    };
+    assert!(type_changed("-rw-r-----", "drwxrwxrwx").is_relaxation());
+    assert!(!type_changed("-rwxr-x---", "drwxr-x---").is_relaxation());
+    assert!(!type_changed("-rw-r-----", "lrwxrwxrwx").is_relaxation());
+    let added = |s| Drift::Added {
+        full_mode: full_mode(s),
+        uid: 0,
+        gid: 0,
+    };
+    assert!(added("-rw-rw-rw-").is_relaxation());
+    assert!(!added("d---------").is_relaxation());
+    assert!(!added("lrwxrwxrwx").is_relaxation());
+    let removed = Drift::Removed {
+        full_mode: full_mode("-rwxrwxrwx"),
+        uid: 0,
+        gid: 0,
+    };
+    assert!(!removed.is_relaxation());
+}
@@ -0,0 +1,219 @@
+use {
+    crate::*,
+    std::fmt::{self, Display, Formatter},
+};
+
+#[cfg(unix)]
+use std::{ffi::CString, fs, io, io::Read, os::unix::ffi::OsStrExt, path::Path};
+
+/// Why the kernel ignores a setuid or setgid bit on execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SetIdIgnoreReason {
+    /// The file is a script starting with a shebang: the
+    /// interpreter is executed, not the file
+    Script,
+    /// The file is on a filesystem mounted with `nosuid`
+    NosuidMount,
+    /// The bit is set without the exec bit it goes with, which
+    /// is displayed as `S`: no exec bit at all for setuid, no
+    /// group exec bit for setgid (which then means mandatory locking)
+    NoExecBit,
+}
+
+impl Display for SetIdIgnoreReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Script => "the file is a script",
+            Self::NosuidMount => "the filesystem is mounted nosuid",
+            Self::NoExecBit => "the matching exec bit isn't set",
+        })
+    }
+}
+
+/// A setuid or setgid bit which has no effect on execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IgnoredSetId {
+    /// Either [`SETUID`] or [`SETGID`]
+    pub bit: ExtraPermission,
+    pub reason: SetIdIgnoreReason,
+}
+
+impl Display for IgnoredSetId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = if self.bit == SETUID {
+            "setuid"
+        } else {
+            "setgid"
+        };
+        write!(f, "{} ignored: {}", name, self.reason)
+    }
+}
+
+/// What executing a file would result in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecOutcome {
+    /// The access of the invoker to the file, which is executable
+    /// only if `access.exec` is true
+    pub access: Access,
+    /// The effective uid of the new process
+    pub euid: u32,
+    /// The effective gid of the new process
+    pub egid: u32,
+    /// The setuid and setgid bits which are set but have no effect
+    pub ignored: Vec<IgnoredSetId>,
+}
+
+impl ExecOutcome {
+    pub fn is_executable(&self) -> bool {
+        self.access.exec
+    }
+    /// Tell whether the process would run with other
+    /// ids than the ones of the invoker
+    pub fn changes_identity(&self, invoker: &Identity) -> bool {
+        self.euid != invoker.uid || self.egid != invoker.gid
+    }
+}
+
+/// Compute the effective uid and gid a process would have when executing
+/// the file, root being considered as having all capabilities.
+///
+/// See [`AccessEvaluator::simulate_exec`].
+pub fn simulate_exec(
+    invoker: &Identity,
+    file: &FileStat,
+    nosuid: bool,
+    script: bool,
+) -> ExecOutcome {
+    AccessEvaluator::privilege_aware(invoker.clone()).simulate_exec(file, nosuid, script)
+}
+
+/// Read the file to know whether it's a script and its filesystem to
+/// know whether it's mounted `nosuid`, then simulate its execution,
+/// root being considered as having all capabilities.
+#[cfg(unix)]
+pub fn inspect_exec<P: AsRef<Path>>(invoker: &Identity, path: P) -> io::Result<ExecOutcome> {
+    let path = path.as_ref();
+    let file = FileStat::read(path)?;
+    let script = file.file_type == FileType::Regular && has_shebang(path)?;
+    let nosuid = is_nosuid_mount(path)?;
+    Ok(simulate_exec(invoker, &file, nosuid, script))
+}
+
+impl AccessEvaluator {
+    /// Compute the effective uid and gid a process would have when
+    /// executing the file, given whether its filesystem is mounted
+    /// `nosuid` and whether it's a script with a shebang.
+    ///
+    /// The setuid bit is honored when the file is executable, even if
+    /// the owner has no exec bit. The setgid bit is honored only when the
+    /// group exec bit is set.
+    ///
+    /// ```
+    /// use umask::*;
+    ///
+    /// let passwd = FileStat::new(FileType::Regular, Mode::from(0o4755), 0, 0);
+    /// let invoker = Identity::new(1000, 1000);
+    /// let outcome = simulate_exec(&invoker, &passwd, false, false);
+    /// assert_eq!((outcome.euid, outcome.egid), (0, 1000));
+    /// let outcome = simulate_exec(&invoker, &passwd, true, false);
+    /// assert_eq!(outcome.euid, 1000);
+    /// assert_eq!(outcome.ignored[0].to_string(), "setuid ignored: the filesystem is mounted nosuid");
+    /// ```
+    pub fn simulate_exec(&self, file: &FileStat, nosuid: bool, script: bool) -> ExecOutcome {
+        let access = self.evaluate(file);
+        let invoker = self.identity();
+        let mut outcome = ExecOutcome {
+            access,
+            euid: invoker.uid,
+            egid: invoker.gid,
+            ignored: Vec::new(),
+        };
+        for (bit, exec_bits) in [(SETUID, ALL_EXEC), (SETGID, GROUP_EXEC)] {
+            if !file.mode.has_extra(bit) {
+                continue;
+            }
+            let reason = if !file.mode.has_any(exec_bits) {
+                Some(SetIdIgnoreReason::NoExecBit)
+            } else if script {
+                Some(SetIdIgnoreReason::Script)
+            } else if nosuid {
+                Some(SetIdIgnoreReason::NosuidMount)
+            } else {
+                None
+            };
+            match reason {
+                Some(reason) => outcome.ignored.push(IgnoredSetId { bit, reason }),
+                None if !access.exec => {}
+                None if bit == SETUID => outcome.euid = file.uid,
+                None => outcome.egid = file.gid,
+            }
+        }
+        outcome
+    }
+}
+
+#[cfg(unix)]
+fn has_shebang(path: &Path) -> io::Result<bool> {
+    let mut start = [0; 2];
+    Ok(read_start(path, &mut start)? == b"#!")
+}
+
+/// Read the first bytes of a file, fewer than the buffer's
+/// length if the file is shorter
+#[cfg(unix)]
+pub(crate) fn read_start<'b>(path: &Path, buf: &'b mut [u8]) -> io::Result<&'b [u8]> {
+    let mut file = fs::File::open(path)?;
+    let mut len = 0;
+    while len < buf.len() {
+        match file.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(&buf[..len])
+}
+
+#[cfg(unix)]
+fn is_nosuid_mount(path: &Path) -> io::Result<bool> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_flag & libc::ST_NOSUID != 0)
+}
+
+#[test]
+fn test_simulate_exec() {
+    let invoker = Identity::new(1000, 1000);
+    let exec = |mode, script| {
+        let file = FileStat::new(FileType::Regular, Mode::from(mode), 0, 50);
+        simulate_exec(&invoker, &file, false, script)
+    };
+    let outcome = exec(0o6755, false);
+    assert_eq!((outcome.euid, outcome.egid), (0, 50));
+    assert!(outcome.ignored.is_empty());
+    assert!(outcome.changes_identity(&invoker));
+    // setgid without group exec: mandatory locking, not setgid execution
+    let outcome = exec(0o6705, false);
+    assert_eq!((outcome.euid, outcome.egid), (0, 1000));
+    assert_eq!(
+        outcome.ignored,
+        vec![IgnoredSetId {
+            bit: SETGID,
+            reason: SetIdIgnoreReason::NoExecBit
+        }]
+    );
+    // "rwSr--r--": nobody can execute it
+    let outcome = exec(0o4644, false);
+    assert!(!outcome.is_executable());
+    assert_eq!(outcome.ignored[0].reason, SetIdIgnoreReason::NoExecBit);
+    assert!(!outcome.changes_identity(&invoker));
+    let outcome = exec(0o4755, true);
+    assert_eq!(outcome.euid, 1000);
+    assert_eq!(
+        outcome.ignored[0].to_string(),
+        "setuid ignored: the file is a script"
+    );
+}
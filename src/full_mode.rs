@@ -0,0 +1,236 @@
+use {
+    crate::*,
+    std::{
+        fmt::{self, Display, Formatter, Write},
+        io,
+        path::Path,
+    },
+};
+
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+/// Mask of the file type bits of a st_mode (`S_IFMT`)
+pub const FILE_TYPE_MASK: u32 = 0o170000;
+
+/// The type of a file, as encoded in the `S_IFMT` bits of a st_mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    Regular,
+    Directory,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl FileType {
+    /// Read the file type from the `S_IFMT` bits of a st_mode
+    pub const fn from_st_mode(st_mode: u32) -> Option<Self> {
+        match st_mode & FILE_TYPE_MASK {
+            0o100000 => Some(Self::Regular),
+            0o040000 => Some(Self::Directory),
+            0o120000 => Some(Self::Symlink),
+            0o010000 => Some(Self::Fifo),
+            0o140000 => Some(Self::Socket),
+            0o060000 => Some(Self::BlockDevice),
+            0o020000 => Some(Self::CharDevice),
+            _ => None,
+        }
+    }
+    /// Return the `S_IFMT` bits of this file type
+    pub const fn bits(self) -> u32 {
+        match self {
+            Self::Regular => 0o100000,
+            Self::Directory => 0o040000,
+            Self::Symlink => 0o120000,
+            Self::Fifo => 0o010000,
+            Self::Socket => 0o140000,
+            Self::BlockDevice => 0o060000,
+            Self::CharDevice => 0o020000,
+        }
+    }
+    /// Return the char used by `ls -l` for this file type
+    pub const fn as_char(self) -> char {
+        match self {
+            Self::Regular => '-',
+            Self::Directory => 'd',
+            Self::Symlink => 'l',
+            Self::Fifo => 'p',
+            Self::Socket => 's',
+            Self::BlockDevice => 'b',
+            Self::CharDevice => 'c',
+        }
+    }
+    /// Read the file type from the char used by `ls -l`
+    pub const fn from_char(c: char) -> Option<Self> {
+        match c {
+            '-' => Some(Self::Regular),
+            'd' => Some(Self::Directory),
+            'l' => Some(Self::Symlink),
+            'p' => Some(Self::Fifo),
+            's' => Some(Self::Socket),
+            'b' => Some(Self::BlockDevice),
+            'c' => Some(Self::CharDevice),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl From<fs::FileType> for FileType {
+    fn from(ft: fs::FileType) -> Self {
+        if ft.is_dir() {
+            Self::Directory
+        } else if ft.is_symlink() {
+            Self::Symlink
+        } else if ft.is_fifo() {
+            Self::Fifo
+        } else if ft.is_socket() {
+            Self::Socket
+        } else if ft.is_block_device() {
+            Self::BlockDevice
+        } else if ft.is_char_device() {
+            Self::CharDevice
+        } else {
+            Self::Regular
+        }
+    }
+}
+
+impl Display for FileType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_char(self.as_char())
+    }
+}
+
+/// A full st_mode: the file type and the [`Mode`]
+///
+/// It displays and parses as the 10 chars form of `ls -l`:
+///
+/// ```
+/// use umask::*;
+///
+/// let full_mode = FullMode::from_st_mode(0o40755).unwrap();
+/// assert_eq!(full_mode.file_type, FileType::Directory);
+/// assert_eq!(full_mode.mode, Mode::from(0o755));
+/// assert_eq!("drwxr-xr-x", full_mode.to_string());
+/// assert_eq!(FullMode::parse("drwxr-xr-x").unwrap(), full_mode);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FullMode {
+    pub file_type: FileType,
+    /// The permission and extra permission bits, without the file type
+    pub mode: Mode,
+}
+
+impl FullMode {
+    pub const fn new(file_type: FileType, mode: Mode) -> Self {
+        Self { file_type, mode }
+    }
+    /// Split a raw st_mode into the file type and the mode.
+    ///
+    /// Return `None` when the file type bits are unknown or absent.
+    pub fn from_st_mode(st_mode: u32) -> Option<Self> {
+        FileType::from_st_mode(st_mode).map(|file_type| Self {
+            file_type,
+            mode: Mode::from(st_mode & !FILE_TYPE_MASK),
+        })
+    }
+    /// Return the raw st_mode
+    pub fn st_mode(self) -> u32 {
+        self.file_type.bits() | (u32::from(self.mode) & !FILE_TYPE_MASK)
+    }
+    /// Return the full mode of the given path, without following
+    /// symbolic links (so that they're seen as `lrwxrwxrwx`, like in `ls -l`).
+    ///
+    /// On non unix platforms, return a regular file or directory with `Mode::all()`
+    pub fn try_from(path: &Path) -> Result<Self, io::Error> {
+        #[cfg(unix)]
+        {
+            let metadata = fs::symlink_metadata(path)?;
+            Ok(Self::from(&metadata))
+        }
+        #[cfg(not(unix))]
+        {
+            let file_type = if path.is_dir() {
+                FileType::Directory
+            } else {
+                FileType::Regular
+            };
+            Ok(Self::new(file_type, Mode::all()))
+        }
+    }
+    /// Try to parse a full mode from the 10 chars form of `ls -l`
+    /// (e.g. `"-rw-r--r--"` or `"lrwxrwxrwx"`).
+    pub fn parse<T: AsRef<str>>(s: T) -> Result<Self, ParseError> {
+        let mut chars = s.as_ref().chars();
+        let file_type = match chars.next() {
+            Some(c) => FileType::from_char(c).ok_or(ParseError::InvalidChar(c, 0))?,
+            None => return Err(ParseError::NotEnoughInput),
+        };
+        let mode = Mode::parse(chars.as_str()).map_err(|e| match e {
+            ParseError::InvalidChar(c, pos) => ParseError::InvalidChar(c, pos + 1),
+            e => e,
+        })?;
+        Ok(Self { file_type, mode })
+    }
+}
+
+#[cfg(unix)]
+impl From<&fs::Metadata> for FullMode {
+    fn from(metadata: &fs::Metadata) -> Self {
+        Self {
+            file_type: metadata.file_type().into(),
+            mode: Mode::from(metadata.mode() & !FILE_TYPE_MASK),
+        }
+    }
+}
+
+impl From<FullMode> for u32 {
+    fn from(full_mode: FullMode) -> Self {
+        full_mode.st_mode()
+    }
+}
+
+impl Display for FullMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.file_type, self.mode)
+    }
+}
+
+impl std::str::FromStr for FullMode {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+#[test]
+fn test_full_mode() -> Result<(), ParseError> {
+    for s in [
+        "drwxr-xr-x",
+        "lrwxrwxrwx",
+        "crw-rw----",
+        "-rwsr-xr-x",
+        "drwxrwxrwt",
+    ] {
+        assert_eq!(FullMode::parse(s)?.to_string(), s);
+    }
+    let full_mode = FullMode::from_st_mode(0o120777).unwrap();
+    assert_eq!(full_mode.file_type, FileType::Symlink);
+    assert_eq!(full_mode.st_mode(), 0o120777);
+    assert_eq!(FullMode::from_st_mode(0o644), None);
+    assert!(matches!(
+        FullMode::parse("xrw-r--r--"),
+        Err(ParseError::InvalidChar('x', 0))
+    ));
+    assert!(matches!(
+        FullMode::parse("-rw-r--r-y"),
+        Err(ParseError::InvalidChar('y', 9))
+    ));
+    Ok(())
+}
@@ -0,0 +1,253 @@
+use {
+    crate::*,
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+    },
+    thiserror::Error,
+};
+
+pub const DEFAULT_PASSWD_PATH: &str = "/etc/passwd";
+pub const DEFAULT_GROUP_PATH: &str = "/etc/group";
+
+/// An error met while loading an [`IdentityDb`]
+#[derive(Debug, Error)]
+pub enum IdentityDbError {
+    #[error("reading {path:?} failed: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    /// A line doesn't have the expected fields (line numbers start at 1)
+    #[error("invalid {file} line {line}: {content:?}")]
+    InvalidLine {
+        file: &'static str,
+        line: usize,
+        content: String,
+    },
+}
+
+/// A user, as described by a line of the passwd file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct User {
+    pub name: String,
+    pub uid: u32,
+    /// The primary group
+    pub gid: u32,
+    pub home: PathBuf,
+    pub shell: PathBuf,
+}
+
+/// A group, as described by a line of the group file
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Group {
+    pub name: String,
+    pub gid: u32,
+    /// Names of the users having this group as supplementary group
+    pub members: Vec<String>,
+}
+
+/// The users and groups of the local passwd and group files
+///
+/// ```
+/// use umask::*;
+///
+/// let db = IdentityDb::parse(
+///     "root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/sh\n",
+///     "root:x:0:\nalice:x:1000:\ndev:x:100:alice,bob\n",
+/// ).unwrap();
+/// assert_eq!(db.uid("alice"), Some(1000));
+/// assert_eq!(db.group_name(100), Some("dev"));
+/// let alice = db.identity("alice").unwrap();
+/// assert_eq!(alice, Identity::new(1000, 1000).with_groups(vec![100]));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdentityDb {
+    pub users: Vec<User>,
+    pub groups: Vec<Group>,
+}
+
+impl IdentityDb {
+    /// Load the database from `/etc/passwd` and `/etc/group`
+    pub fn load() -> Result<Self, IdentityDbError> {
+        Self::load_from(DEFAULT_PASSWD_PATH, DEFAULT_GROUP_PATH)
+    }
+    /// Load the database from the given passwd and group files
+    pub fn load_from<P: AsRef<Path>, G: AsRef<Path>>(
+        passwd_path: P,
+        group_path: G,
+    ) -> Result<Self, IdentityDbError> {
+        let read = |path: &Path| {
+            fs::read_to_string(path).map_err(|source| IdentityDbError::Read {
+                path: path.to_path_buf(),
+                source,
+            })
+        };
+        let passwd = read(passwd_path.as_ref())?;
+        let group = read(group_path.as_ref())?;
+        Self::parse(&passwd, &group)
+    }
+    /// Parse the content of a passwd file and of a group file.
+    ///
+    /// Empty lines, comments and NIS compat lines (starting
+    /// with `+` or `-`) are ignored.
+    pub fn parse(passwd: &str, group: &str) -> Result<Self, IdentityDbError> {
+        let mut db = Self::default();
+        for (line, fields) in records(passwd) {
+            let invalid = || IdentityDbError::InvalidLine {
+                file: "passwd",
+                line,
+                content: fields.join(":"),
+            };
+            if fields.len() != 7 {
+                return Err(invalid());
+            }
+            db.users.push(User {
+                name: fields[0].to_string(),
+                uid: fields[2].parse().map_err(|_| invalid())?,
+                gid: fields[3].parse().map_err(|_| invalid())?,
+                home: fields[5].into(),
+                shell: fields[6].into(),
+            });
+        }
+        for (line, fields) in records(group) {
+            let invalid = || IdentityDbError::InvalidLine {
+                file: "group",
+                line,
+                content: fields.join(":"),
+            };
+            if fields.len() != 4 {
+                return Err(invalid());
+            }
+            db.groups.push(Group {
+                name: fields[0].to_string(),
+                gid: fields[2].parse().map_err(|_| invalid())?,
+                members: fields[3]
+                    .split(',')
+                    .filter(|member| !member.is_empty())
+                    .map(|member| member.to_string())
+                    .collect(),
+            });
+        }
+        Ok(db)
+    }
+    pub fn user_by_name(&self, name: &str) -> Option<&User> {
+        self.users.iter().find(|user| user.name == name)
+    }
+    pub fn user_by_uid(&self, uid: u32) -> Option<&User> {
+        self.users.iter().find(|user| user.uid == uid)
+    }
+    pub fn group_by_name(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+    pub fn group_by_gid(&self, gid: u32) -> Option<&Group> {
+        self.groups.iter().find(|group| group.gid == gid)
+    }
+    pub fn uid(&self, user_name: &str) -> Option<u32> {
+        self.user_by_name(user_name).map(|user| user.uid)
+    }
+    pub fn user_name(&self, uid: u32) -> Option<&str> {
+        self.user_by_uid(uid).map(|user| user.name.as_str())
+    }
+    pub fn gid(&self, group_name: &str) -> Option<u32> {
+        self.group_by_name(group_name).map(|group| group.gid)
+    }
+    pub fn group_name(&self, gid: u32) -> Option<&str> {
+        self.group_by_gid(gid).map(|group| group.name.as_str())
+    }
+    /// Return the name of the user, or the uid when it's unknown,
+    /// like the owner column of `ls -l`
+    pub fn user_label(&self, uid: u32) -> String {
+        self.user_name(uid)
+            .map_or_else(|| uid.to_string(), |name| name.to_string())
+    }
+    /// Return the name of the group, or the gid when it's unknown,
+    /// like the group column of `ls -l`
+    pub fn group_label(&self, gid: u32) -> String {
+        self.group_name(gid)
+            .map_or_else(|| gid.to_string(), |name| name.to_string())
+    }
+    /// Return the gids of all the groups of the user, the
+    /// primary group first, without duplicates
+    pub fn group_ids_of(&self, user_name: &str) -> Vec<u32> {
+        let mut gids = Vec::new();
+        if let Some(user) = self.user_by_name(user_name) {
+            gids.push(user.gid);
+        }
+        for group in &self.groups {
+            if !gids.contains(&group.gid) && group.members.iter().any(|m| m == user_name) {
+                gids.push(group.gid);
+            }
+        }
+        gids
+    }
+    /// Return all the groups the user belongs to, the primary group first
+    pub fn groups_of(&self, user_name: &str) -> Vec<&Group> {
+        self.group_ids_of(user_name)
+            .into_iter()
+            .filter_map(|gid| self.group_by_gid(gid))
+            .collect()
+    }
+    /// Return the identity the user gets when logging in: its uid,
+    /// its primary group and its supplementary groups
+    pub fn identity(&self, user_name: &str) -> Option<Identity> {
+        let user = self.user_by_name(user_name)?;
+        let groups = self
+            .group_ids_of(user_name)
+            .into_iter()
+            .filter(|&gid| gid != user.gid)
+            .collect();
+        Some(Identity::new(user.uid, user.gid).with_groups(groups))
+    }
+}
+
+/// Return the colon separated fields of the meaningful lines,
+/// with their line number
+fn records(content: &str) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !(line.is_empty() || line.starts_with(['#', '+', '-']))
+        })
+        .map(|(idx, line)| (idx + 1, line.split(':').collect()))
+}
+
+#[test]
+fn test_identity_db() {
+    let passwd = "\
+# local users
+root:x:0:0:root:/root:/bin/bash
+alice:x:1000:1000:Alice:/home/alice:/bin/bash
+bob:x:1001:1001::/home/bob:/bin/sh
++@netgroup::::::
+";
+    let group = "\
+root:x:0:
+alice:x:1000:
+bob:x:1001:
+dev:x:100:alice,bob
+ops:x:200:bob
+wheel:x:10:
+";
+    let db = IdentityDb::parse(passwd, group).unwrap();
+    assert_eq!(db.users.len(), 3);
+    assert_eq!(db.user_name(1001), Some("bob"));
+    assert_eq!(db.gid("ops"), Some(200));
+    assert_eq!(db.user_label(4242), "4242");
+    assert_eq!(db.group_label(10), "wheel");
+    let names: Vec<_> = db
+        .groups_of("bob")
+        .iter()
+        .map(|g| g.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["bob", "dev", "ops"]);
+    assert_eq!(
+        db.identity("bob"),
+        Some(Identity::new(1001, 1001).with_groups(vec![100, 200]))
+    );
+    assert_eq!(db.identity("carol"), None);
+    let err = IdentityDb::parse("alice:x:nope:1000::/:/bin/sh", "").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid passwd line 1: \"alice:x:nope:1000::/:/bin/sh\""
+    );
+}
@@ -14,6 +14,11 @@
 //! let mode: Mode = "rw-rw-r--".parse().unwrap();
 //! assert_eq!("rw-rw-r--", mode.to_string());
 //!
+//! // Including an octal one, and you can format as octal too:
+//! let mode = Mode::parse_octal("0755").unwrap();
+//! assert_eq!("rwxr-xr-x", mode.to_string());
+//! assert_eq!("755", format!("{:o}", mode));
+//!
 //! // You may use `|` to combine class permissions:
 //! let mu = Mode::from(0o640);
 //! let mo = Mode::from(0o044);
@@ -56,6 +61,64 @@
 //! assert_eq!("rwxrwxrwx", m.without_any_extra().to_string());
 //!
 //! ```
+mod access;
+#[cfg(unix)]
+mod apply;
+#[cfg(unix)]
+mod audit;
+mod change;
+#[cfg(unix)]
+mod compare;
+mod creation;
+#[cfg(unix)]
+mod drift;
+mod exec;
+mod full_mode;
+mod identity_db;
 mod mode;
+mod mode_diff;
+mod notation;
+#[cfg(unix)]
+mod path_access;
+#[cfg(unix)]
+mod path_escape;
+#[cfg(unix)]
+mod plan;
+#[cfg(feature = "policy")]
+mod policy;
+#[cfg(all(unix, feature = "policy"))]
+mod reconcile;
+#[cfg(unix)]
+mod recursive;
+mod removal;
+#[cfg(unix)]
+mod snapshot;
+#[cfg(all(unix, test))]
+mod test_util;
+mod umask;
+#[cfg(unix)]
+mod walk;
+mod who;
+
+pub use {
+    access::*, change::*, creation::*, exec::*, full_mode::*, identity_db::*, mode::*,
+    mode_diff::*, notation::*, removal::*, umask::*, who::*,
+};
+
+#[cfg(unix)]
+pub use {
+    apply::*, audit::*, compare::*, drift::*, path_access::*, plan::*, recursive::*, snapshot::*,
+    walk::*,
+};
+
+#[cfg(feature = "policy")]
+pub use policy::*;
+
+#[cfg(unix)]
+use path_escape::*;
+
+#[cfg(all(unix, test))]
+use test_util::*;
 
-pub use mode::*;
+#[cfg(all(unix, feature = "policy"))]
+pub use reconcile::*;
@@ -165,6 +165,26 @@ impl Display for Mode {
     }
 }
 
+impl fmt::Octal for Mode {
+    /// Formats the permission and extra permission bits as octal,
+    /// with 3 digits (`"644"`), or 4 when an extra permission bit
+    /// is set (`"4755"`).
+    ///
+    /// With the alternate flag (`{:#o}`), the output is prefixed with `0o`.
+    ///
+    /// The output can be parsed back with [`Mode::parse_octal()`].
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            f.write_str("0o")?;
+        }
+        if self.value & EXTRA != 0 {
+            write!(f, "{:04o}", self.value & (EXTRA | ALL))
+        } else {
+            write!(f, "{:03o}", self.value & ALL)
+        }
+    }
+}
+
 /// Parsing error.
 #[derive(Debug, Error)]
 pub enum ParseError {
@@ -176,6 +196,12 @@ pub enum ParseError {
     /// Trailing characters.
     #[error("trailing characters")]
     TrailingCharacters,
+    /// Invalid digit in an octal mode.
+    #[error("invalid octal digit '{0}' at position {1}")]
+    InvalidOctalDigit(char, usize),
+    /// Octal mode greater than `0o7777`.
+    #[error("octal mode out of range: {0}")]
+    OctalOutOfRange(String),
 }
 
 /// Represents Permissions for a file.
@@ -201,6 +227,9 @@ impl Mode {
     }
     /// Return the mode for the given path.
     /// On non unix platforms, return `Mode::all()`
+    ///
+    /// The returned value includes the file type bits of the st_mode.
+    /// Use [`crate::FullMode::try_from`] to get the file type and the mode apart.
     #[allow(unused_variables)]
     pub fn try_from(path: &Path) -> Result<Self, io::Error> {
         #[cfg(unix)]
@@ -267,6 +296,32 @@ impl Mode {
             Err(ParseError::TrailingCharacters)
         }
     }
+    /// Try to parse a mode from its octal representation, with or
+    /// without leading `0` or `0o` (e.g. `"755"`, `"0755"`, `"0o4755"`).
+    ///
+    /// Values greater than `0o7777` are rejected.
+    pub fn parse_octal<T: AsRef<str>>(s: T) -> Result<Self, ParseError> {
+        let s = s.as_ref();
+        let (digits, offset) = match s.strip_prefix("0o") {
+            Some(digits) => (digits, 2),
+            None => (s, 0),
+        };
+        if digits.is_empty() {
+            return Err(ParseError::NotEnoughInput);
+        }
+        let mut value: u32 = 0;
+        for (pos, c) in digits.chars().enumerate() {
+            let digit = match c.to_digit(8) {
+                Some(digit) => digit,
+                None => return Err(ParseError::InvalidOctalDigit(c, pos + offset)),
+            };
+            value = value * 8 + digit;
+            if value > 0o7777 {
+                return Err(ParseError::OctalOutOfRange(s.to_string()));
+            }
+        }
+        Ok(Self { value })
+    }
     /// Finds if the mode indicates an executable file
     #[inline(always)]
     pub const fn is_exe(self) -> bool {
@@ -277,6 +332,11 @@ impl Mode {
     pub const fn has(self, other: Self) -> bool {
         self.value & other.value == other.value
     }
+    /// Indicates whether at least one of the passed class/permissions is present in self
+    #[inline(always)]
+    pub const fn has_any(self, other: Self) -> bool {
+        self.value & other.value != 0
+    }
     /// Indicates whether the passed extra permission is present in self
     #[inline(always)]
     pub const fn has_extra(self, other: ExtraPermission) -> bool {
@@ -321,6 +381,13 @@ impl Mode {
             value: self.value & !(class & perm),
         }
     }
+    /// keep only the class/permissions present in both modes
+    #[inline(always)]
+    pub const fn intersection(self, other: Mode) -> Self {
+        Self {
+            value: self.value & other.value,
+        }
+    }
     /// add the class/permissions of the other mode
     #[inline(always)]
     pub const fn with(self, other: Mode) -> Self {
@@ -410,3 +477,39 @@ fn test_try_from_str() -> Result<(), ParseError> {
     );
     Ok(())
 }
+
+#[test]
+fn test_octal() -> Result<(), ParseError> {
+    assert_eq!(Mode::parse_octal("755")?, Mode::from(0o755));
+    assert_eq!(Mode::parse_octal("0755")?, Mode::from(0o755));
+    assert_eq!(Mode::parse_octal("0o4755")?, Mode::from(0o4755));
+    assert_eq!(Mode::parse_octal("2775")?, Mode::from(0o2775));
+    assert!(matches!(
+        Mode::parse_octal(""),
+        Err(ParseError::NotEnoughInput)
+    ));
+    assert!(matches!(
+        Mode::parse_octal("0o"),
+        Err(ParseError::NotEnoughInput)
+    ));
+    assert!(matches!(
+        Mode::parse_octal("0o758"),
+        Err(ParseError::InvalidOctalDigit('8', 4))
+    ));
+    assert!(matches!(
+        Mode::parse_octal("17777"),
+        Err(ParseError::OctalOutOfRange(_))
+    ));
+
+    assert_eq!("644", format!("{:o}", Mode::from(0o644)));
+    assert_eq!("000", format!("{:o}", Mode::new()));
+    assert_eq!("4755", format!("{:o}", Mode::from(0o4755)));
+    assert_eq!("0o1777", format!("{:#o}", Mode::from(0o1777)));
+    assert_eq!("755", format!("{:o}", Mode::from(0o40755))); // file type bits are ignored
+    for value in [0o644, 0o755, 0o2775, 0o7777] {
+        let mode = Mode::from(value);
+        assert_eq!(Mode::parse_octal(format!("{:o}", mode))?, mode);
+        assert_eq!(Mode::parse_octal(format!("{:#o}", mode))?, mode);
+    }
+    Ok(())
+}
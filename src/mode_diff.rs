@@ -0,0 +1,216 @@
+use {
+    crate::*,
+    std::fmt::{self, Display, Formatter},
+};
+
+/// The bits added and removed when going from a mode to another one,
+/// as returned by [`Mode::diff`]
+///
+/// It displays as the symbolic chmod expression doing the change
+/// (e.g. `g-w,o-r`), and [`ModeDiff::explain`] describes it in prose.
+///
+/// ```
+/// use umask::*;
+///
+/// let diff = Mode::from(0o2754).diff(Mode::from(0o770));
+/// assert_eq!(diff.to_string(), "g-s,o-r,g+w");
+/// assert_eq!(
+///     diff.explain(),
+///     "group can now write; others can no longer read; setgid was removed",
+/// );
+/// assert_eq!(diff.added_for(GROUP), GROUP_WRITE);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct ModeDiff {
+    /// The bits which are only in the new mode
+    pub added: Mode,
+    /// The bits which are only in the old mode
+    pub removed: Mode,
+}
+
+impl Mode {
+    /// Return the bits to add and remove to go from this mode to the other one
+    pub fn diff(self, other: Mode) -> ModeDiff {
+        let bits = Mode::from(EXTRA | ALL);
+        ModeDiff {
+            added: other & !self & bits,
+            removed: self & !other & bits,
+        }
+    }
+}
+
+impl ModeDiff {
+    /// Tell whether both modes are the same
+    pub fn is_empty(self) -> bool {
+        self.added == Mode::new() && self.removed == Mode::new()
+    }
+    /// Return the bits added for a class ([`USER`], [`GROUP`], [`OTHERS`]
+    /// or [`EXTRA`] for the setuid, setgid and sticky bits)
+    pub fn added_for(self, class: Class) -> Mode {
+        self.added & Mode::from(class)
+    }
+    /// Return the bits removed for a class ([`USER`], [`GROUP`], [`OTHERS`]
+    /// or [`EXTRA`] for the setuid, setgid and sticky bits)
+    pub fn removed_for(self, class: Class) -> Mode {
+        self.removed & Mode::from(class)
+    }
+    /// Return the change in symbolic notation, removing then adding the bits
+    pub fn to_change(self) -> ModeChange {
+        ModeChange::between(self.removed, self.added)
+    }
+    /// Return the bits of the diff one by one, the permissions
+    /// of the owner, group and others first, then setuid, setgid and sticky
+    pub fn bit_changes(self) -> Vec<BitChange> {
+        let bits = [USER, GROUP, OTHERS]
+            .iter()
+            .flat_map(|&class| [READ, WRITE, EXEC].map(|perm| class & perm))
+            .chain([SETUID, SETGID, STICKY]);
+        let mut changes = Vec::new();
+        for bit in bits.map(Mode::from) {
+            if self.added.has(bit) {
+                changes.push(BitChange { bit, added: true });
+            } else if self.removed.has(bit) {
+                changes.push(BitChange { bit, added: false });
+            }
+        }
+        changes
+    }
+    /// Describe the change in prose, e.g.
+    /// "owner can now execute; others can no longer read or write; sticky bit was added"
+    ///
+    /// Return "no change" when the diff is empty.
+    pub fn explain(self) -> String {
+        let mut sentences = Vec::new();
+        for (name, class) in [("owner", USER), ("group", GROUP), ("others", OTHERS)] {
+            for (bits, verb, conjunction) in [
+                (self.added_for(class), "can now", "and"),
+                (self.removed_for(class), "can no longer", "or"),
+            ] {
+                let perms: Vec<&str> = [(READ, "read"), (WRITE, "write"), (EXEC, "execute")]
+                    .iter()
+                    .filter(|(perm, _)| bits.has_any(Mode::from(class & perm)))
+                    .map(|&(_, perm_name)| perm_name)
+                    .collect();
+                if !perms.is_empty() {
+                    sentences.push(format!(
+                        "{} {} {}",
+                        name,
+                        verb,
+                        enumerate(&perms, conjunction)
+                    ));
+                }
+            }
+        }
+        for (extra, name) in [
+            (SETUID, "setuid"),
+            (SETGID, "setgid"),
+            (STICKY, "sticky bit"),
+        ] {
+            if self.added.has_extra(extra) {
+                sentences.push(format!("{} was added", name));
+            } else if self.removed.has_extra(extra) {
+                sentences.push(format!("{} was removed", name));
+            }
+        }
+        if sentences.is_empty() {
+            return "no change".to_string();
+        }
+        sentences.join("; ")
+    }
+}
+
+/// Join words as "a, b and c"
+fn enumerate(words: &[&str], conjunction: &str) -> String {
+    match words.split_last() {
+        Some((last, head)) if !head.is_empty() => {
+            format!("{} {} {}", head.join(", "), conjunction, last)
+        }
+        _ => words.join(""),
+    }
+}
+
+impl Display for ModeDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_change())
+    }
+}
+
+/// A permission bit set in only one of two modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitChange {
+    /// The single bit, e.g. [`GROUP_WRITE`] or `Mode::from(STICKY)`
+    pub bit: Mode,
+    /// Whether the bit is in the second mode only
+    pub added: bool,
+}
+
+impl BitChange {
+    /// Return the bits differing between two modes, the permissions
+    /// of the owner, group and others first, then setuid, setgid and sticky
+    pub fn list(old: Mode, new: Mode) -> Vec<Self> {
+        old.diff(new).bit_changes()
+    }
+}
+
+impl Display for BitChange {
+    /// Formats the change as e.g. "group lost write" or "sticky added"
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let bits = u32::from(self.bit);
+        let extra = match bits {
+            SETUID => Some("setuid"),
+            SETGID => Some("setgid"),
+            STICKY => Some("sticky"),
+            _ => None,
+        };
+        if let Some(extra) = extra {
+            let verb = if self.added { "added" } else { "removed" };
+            return write!(f, "{} {}", extra, verb);
+        }
+        let class = if bits & USER != 0 {
+            "owner"
+        } else if bits & GROUP != 0 {
+            "group"
+        } else {
+            "others"
+        };
+        let perm = if bits & READ != 0 {
+            "read"
+        } else if bits & WRITE != 0 {
+            "write"
+        } else {
+            "execute"
+        };
+        let verb = if self.added { "gained" } else { "lost" };
+        write!(f, "{} {} {}", class, verb, perm)
+    }
+}
+
+#[test]
+fn test_mode_diff() {
+    let diff = |old: u32, new: u32| Mode::from(old).diff(Mode::from(new));
+    assert!(diff(0o644, 0o644).is_empty());
+    assert_eq!(diff(0o644, 0o644).explain(), "no change");
+    assert_eq!(diff(0o644, 0o644).to_string(), "");
+    let d = diff(0o4644, 0o1751);
+    assert_eq!(d.added_for(USER), USER_EXEC);
+    assert_eq!(d.removed_for(OTHERS), OTHERS_READ);
+    assert_eq!(d.added_for(EXTRA), Mode::from(STICKY));
+    assert_eq!(
+        d.explain(),
+        "owner can now execute; group can now execute; others can now execute; \
+            others can no longer read; setuid was removed; sticky bit was added"
+    );
+    assert_eq!(d.to_string(), "u-s,o-r,ug+x,o+xt");
+    assert_eq!(d.to_change().apply(Mode::from(0o4644)), Mode::from(0o1751));
+    assert_eq!(
+        diff(0o777, 0o700).explain(),
+        "group can no longer read, write or execute; others can no longer read, write or execute"
+    );
+    let changes: Vec<String> = BitChange::list(Mode::from(0o775), Mode::from(0o1755))
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    assert_eq!(changes, vec!["group lost write", "sticky added"]);
+    let change = BitChange::list(Mode::from(0o644), Mode::from(0o744));
+    assert_eq!(change[0].to_string(), "owner gained execute");
+}
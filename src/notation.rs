@@ -0,0 +1,120 @@
+use {
+    crate::*,
+    std::fmt::{self, Display, Formatter},
+    thiserror::Error,
+};
+
+/// One of the notations a mode can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Notation {
+    /// Octal number, e.g. `644`, `0755` or `0o4755`
+    Octal,
+    /// 9 characters, e.g. `rw-r--r--`
+    Symbolic,
+    /// 10 characters, as displayed by `ls -l`, e.g. `-rw-r--r--`
+    Ls,
+    /// chmod(1) symbolic clauses, e.g. `u=rw,go=r`
+    Chmod,
+}
+
+impl Notation {
+    /// All notations, in the order [`Mode::parse_any`] tries them
+    pub const ALL: [Notation; 4] = [Self::Octal, Self::Symbolic, Self::Ls, Self::Chmod];
+    fn parse(self, s: &str) -> Result<Mode, ParseError> {
+        match self {
+            Self::Octal => Mode::parse_octal(s),
+            Self::Symbolic => Mode::parse(s),
+            Self::Ls => FullMode::parse(s).map(|full_mode| full_mode.mode),
+            Self::Chmod => ModeChange::parse(s)
+                .map(|change| change.apply_with_umask(Mode::new(), false, Mode::new())),
+        }
+    }
+}
+
+impl Display for Notation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Octal => "octal",
+            Self::Symbolic => "symbolic",
+            Self::Ls => "ls",
+            Self::Chmod => "chmod",
+        })
+    }
+}
+
+/// Error returned by [`Mode::parse_any`] when the input
+/// matches no notation.
+///
+/// It holds, for every notation tried, the reason it was rejected.
+#[derive(Debug, Error)]
+#[error("unrecognized mode {input:?}{}", attempts_text(.attempts))]
+pub struct ParseAnyError {
+    pub input: String,
+    pub attempts: Vec<(Notation, ParseError)>,
+}
+
+fn attempts_text(attempts: &[(Notation, ParseError)]) -> String {
+    attempts
+        .iter()
+        .map(|(notation, error)| format!("; {} notation: {}", notation, error))
+        .collect()
+}
+
+impl Mode {
+    /// Parse a mode written in any of the supported notations,
+    /// and tell which one was recognized.
+    ///
+    /// ```
+    /// use umask::*;
+    ///
+    /// assert_eq!(Mode::parse_any("644").unwrap(), (Mode::from(0o644), Notation::Octal));
+    /// assert_eq!(Mode::parse_any("rw-r--r--").unwrap(), (Mode::from(0o644), Notation::Symbolic));
+    /// assert_eq!(Mode::parse_any("-rw-r--r--").unwrap(), (Mode::from(0o644), Notation::Ls));
+    /// assert_eq!(Mode::parse_any("u=rw,go=r").unwrap(), (Mode::from(0o644), Notation::Chmod));
+    /// ```
+    ///
+    /// chmod clauses are applied to an empty mode, and the umask
+    /// isn't used for clauses without "who", so `+r` gives `r--r--r--`.
+    pub fn parse_any<T: AsRef<str>>(s: T) -> Result<(Self, Notation), ParseAnyError> {
+        let s = s.as_ref();
+        let mut attempts = Vec::new();
+        for notation in Notation::ALL {
+            match notation.parse(s) {
+                Ok(mode) => return Ok((mode, notation)),
+                Err(e) => attempts.push((notation, e)),
+            }
+        }
+        Err(ParseAnyError {
+            input: s.to_string(),
+            attempts,
+        })
+    }
+}
+
+#[test]
+fn test_parse_any() {
+    assert_eq!(
+        Mode::parse_any("0o2775").unwrap(),
+        (Mode::from(0o2775), Notation::Octal)
+    );
+    assert_eq!(
+        Mode::parse_any("drwxrwsr-x").unwrap(),
+        (Mode::from(0o2775), Notation::Ls)
+    );
+    assert_eq!(
+        Mode::parse_any("+r").unwrap(),
+        (Mode::from(0o444), Notation::Chmod)
+    );
+    let err = Mode::parse_any("rw-r--r-y").unwrap_err();
+    let notations: Vec<Notation> = err.attempts.iter().map(|(n, _)| *n).collect();
+    assert_eq!(notations, Notation::ALL);
+    assert!(matches!(err.attempts[1].1, ParseError::InvalidChar('y', 8)));
+    assert_eq!(
+        err.to_string(),
+        "unrecognized mode \"rw-r--r-y\"; \
+        octal notation: invalid octal digit 'r' at position 0; \
+        symbolic notation: invalid character 'y' at position 8; \
+        ls notation: invalid character 'r' at position 0; \
+        chmod notation: invalid character 'r' at position 0"
+    );
+}
@@ -0,0 +1,246 @@
+use {
+    crate::*,
+    std::{
+        collections::VecDeque,
+        env,
+        ffi::OsString,
+        fmt::{self, Display, Formatter},
+        fs, io,
+        path::{Component, Path, PathBuf},
+    },
+};
+
+/// Maximal number of symbolic links followed when resolving
+/// a path, like the Linux kernel
+const MAX_SYMLINKS: usize = 40;
+
+/// Why a path can't be accessed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathBlock {
+    /// The path, with symbolic links resolved, of the
+    /// component which blocks access
+    pub path: PathBuf,
+    /// Whether the component is a directory which had to be searched
+    pub search: bool,
+    /// The permissions that were needed on this component
+    /// ([`EXEC`] when it's a directory which had to be searched)
+    pub needed: Permission,
+    /// The evaluated access on the component
+    pub access: Access,
+}
+
+impl Display for PathBlock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.search {
+            write!(f, "search")?;
+        } else {
+            let mut names = Vec::new();
+            for (perm, name) in [(READ, "read"), (WRITE, "write"), (EXEC, "execute")] {
+                if self.needed & perm != 0 {
+                    names.push(name);
+                }
+            }
+            write!(f, "{}", names.join("+"))?;
+        }
+        write!(f, " permission denied on {:?}", self.path)?;
+        write!(f, " ({}), {}", self.access.mode, self.access)
+    }
+}
+
+/// The result of a path access check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathAccess {
+    /// All the components can be searched and the target
+    /// has the wanted permissions
+    Allowed {
+        /// The target, with symbolic links resolved
+        path: PathBuf,
+        access: Access,
+    },
+    /// A component blocks access
+    Blocked(PathBlock),
+}
+
+impl PathAccess {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Self::Allowed { .. })
+    }
+}
+
+/// Check whether the identity can access the path with the wanted
+/// permissions (a combination of [`READ`], [`WRITE`] and [`EXEC`]),
+/// checking search permission on every traversed directory.
+///
+/// Root is considered as having all capabilities (see
+/// [`AccessEvaluator::privilege_aware`]). Use [`AccessEvaluator::check_path`]
+/// to specify capabilities or to ignore them.
+///
+/// ```no_run
+/// use umask::*;
+///
+/// let identity = Identity::new(1000, 1000);
+/// match check_path_access("/srv/app/data/file", &identity, READ).unwrap() {
+///     PathAccess::Allowed { .. } => println!("ok"),
+///     PathAccess::Blocked(block) => println!("{}", block),
+/// }
+/// ```
+pub fn check_path_access<P: AsRef<Path>>(
+    path: P,
+    identity: &Identity,
+    wanted: Permission,
+) -> io::Result<PathAccess> {
+    AccessEvaluator::privilege_aware(identity.clone()).check_path(path, wanted)
+}
+
+impl AccessEvaluator {
+    /// Check whether the path can be accessed with the wanted permissions,
+    /// resolving it like the kernel does: every directory to look into
+    /// must be searchable, symbolic links are followed (including the
+    /// last one) and `..` goes to the parent of the directory the
+    /// previous components lead to.
+    ///
+    /// A relative path is resolved from the current directory.
+    ///
+    /// An error is returned when the path can't be resolved (a
+    /// component is missing, isn't a directory, too many symbolic links, etc.)
+    pub fn check_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+        wanted: Permission,
+    ) -> io::Result<PathAccess> {
+        let path = path.as_ref();
+        let mut current = if path.is_absolute() {
+            PathBuf::from("/")
+        } else {
+            env::current_dir()?
+        };
+        let mut pending: VecDeque<OsString> = components(path).collect();
+        let mut followed_symlinks = 0;
+        while let Some(name) = pending.pop_front() {
+            let dir = FileStat::from(&fs::metadata(&current)?);
+            let access = self.evaluate(&dir);
+            if !access.exec {
+                return Ok(PathAccess::Blocked(PathBlock {
+                    path: current,
+                    search: true,
+                    needed: EXEC,
+                    access,
+                }));
+            }
+            if name == "." {
+                continue;
+            }
+            if name == ".." {
+                current.pop(); // the root is its own parent
+                continue;
+            }
+            let child = current.join(&name);
+            let metadata = fs::symlink_metadata(&child)?;
+            if metadata.file_type().is_symlink() {
+                followed_symlinks += 1;
+                if followed_symlinks > MAX_SYMLINKS {
+                    return Err(io::Error::from_raw_os_error(libc::ELOOP));
+                }
+                let target = fs::read_link(&child)?;
+                if target.is_absolute() {
+                    current = PathBuf::from("/");
+                }
+                for component in components(&target).rev() {
+                    pending.push_front(component);
+                }
+                continue;
+            }
+            if !pending.is_empty() && !metadata.is_dir() {
+                return Err(io::Error::from_raw_os_error(libc::ENOTDIR));
+            }
+            current = child;
+        }
+        let target = FileStat::from(&fs::metadata(&current)?);
+        let access = self.evaluate(&target);
+        Ok(if access.allows(wanted) {
+            PathAccess::Allowed {
+                path: current,
+                access,
+            }
+        } else {
+            PathAccess::Blocked(PathBlock {
+                path: current,
+                search: false,
+                needed: wanted,
+                access,
+            })
+        })
+    }
+}
+
+/// Return the names to look up, in order
+fn components(path: &Path) -> impl DoubleEndedIterator<Item = OsString> + '_ {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name.to_os_string()),
+        Component::CurDir => Some(".".into()),
+        Component::ParentDir => Some("..".into()),
+        Component::RootDir | Component::Prefix(_) => None,
+    })
+}
+
+#[test]
+fn test_check_path_access() {
+    let root = TestDir::new("path-access");
+    fs::create_dir_all(root.join("private")).unwrap();
+    fs::create_dir_all(root.join("public")).unwrap();
+    fs::write(root.join("private/file"), "").unwrap();
+    fs::write(root.join("public/file"), "").unwrap();
+    std::os::unix::fs::symlink("../private/file", root.join("public/link")).unwrap();
+    Mode::from(0o755).apply_to(&root).unwrap();
+    Mode::from(0o700).apply_to(root.join("private")).unwrap();
+    Mode::from(0o755).apply_to(root.join("public")).unwrap();
+    Mode::from(0o644)
+        .apply_to(root.join("private/file"))
+        .unwrap();
+    Mode::from(0o640)
+        .apply_to(root.join("public/file"))
+        .unwrap();
+    let owner = FileStat::read(&root).unwrap();
+    let stranger = Identity::new(owner.uid.wrapping_add(12345), owner.gid.wrapping_add(12345));
+    let stranger = AccessEvaluator::new(stranger);
+    let check = |path: &str, wanted| stranger.check_path(root.join(path), wanted).unwrap();
+
+    // the file is readable by others, but not the directory
+    let private = root.canonicalize().unwrap().join("private");
+    match check("private/file", READ) {
+        PathAccess::Blocked(block) => {
+            assert_eq!(block.path, private);
+            assert_eq!(block.needed, EXEC);
+            assert!(block
+                .to_string()
+                .starts_with("search permission denied on "));
+        }
+        _ => panic!("access should be blocked"),
+    }
+    // same through a symbolic link, or through ..
+    assert!(matches!(
+        check("public/link", READ),
+        PathAccess::Blocked(PathBlock { path, .. }) if path == private
+    ));
+    assert!(matches!(
+        check("public/../private/file", READ),
+        PathAccess::Blocked(PathBlock { path, .. }) if path == private
+    ));
+    // all dirs are searchable, but the file isn't readable by others
+    match check("public/file", READ | WRITE) {
+        PathAccess::Blocked(block) => {
+            assert!(!block.search);
+            assert!(block
+                .to_string()
+                .starts_with("read+write permission denied on "));
+        }
+        _ => panic!("access should be blocked"),
+    }
+    assert!(check("public/./../public", READ | EXEC).is_allowed());
+    // the owner can read the private file through the link
+    let owner = AccessEvaluator::new(Identity::new(owner.uid, owner.gid));
+    assert!(owner
+        .check_path(root.join("public/link"), READ)
+        .unwrap()
+        .is_allowed());
+}
@@ -0,0 +1,63 @@
+use std::{
+    ffi::OsString,
+    fmt::{self, Formatter, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+};
+
+/// Write a path with the backslashes, the ASCII control characters and
+/// the bytes which aren't valid UTF-8 escaped, so that it fits on a line
+pub(crate) fn escape_path(path: &Path, f: &mut Formatter<'_>) -> fmt::Result {
+    let mut rest = path.as_os_str().as_bytes();
+    while !rest.is_empty() {
+        let (valid, invalid) = match std::str::from_utf8(rest) {
+            Ok(valid) => (valid, 0),
+            Err(e) => {
+                let valid = std::str::from_utf8(&rest[..e.valid_up_to()]).unwrap_or_default();
+                (valid, e.error_len().unwrap_or(rest.len() - e.valid_up_to()))
+            }
+        };
+        for c in valid.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                c if c.is_control() && c.is_ascii() => write!(f, "\\x{:02x}", c as u8)?,
+                c => f.write_char(c)?,
+            }
+        }
+        rest = &rest[valid.len()..];
+        for byte in &rest[..invalid] {
+            write!(f, "\\x{:02x}", byte)?;
+        }
+        rest = &rest[invalid..];
+    }
+    Ok(())
+}
+
+/// Read back a path written by [`escape_path`]
+pub(crate) fn unescape_path(s: &str) -> Option<PathBuf> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        if byte != b'\\' {
+            bytes.push(byte);
+            continue;
+        }
+        match rest {
+            [b'\\', tail @ ..] => {
+                bytes.push(b'\\');
+                rest = tail;
+            }
+            [b'x', h, l, tail @ ..] => {
+                let hex = std::str::from_utf8(&[*h, *l]).ok()?.to_string();
+                bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+                rest = tail;
+            }
+            _ => return None,
+        }
+    }
+    Some(OsString::from_vec(bytes).into())
+}
@@ -0,0 +1,184 @@
+use {
+    crate::*,
+    std::{
+        fmt::{self, Display, Formatter},
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+/// A mode change computed but not yet applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedChange {
+    pub path: PathBuf,
+    pub file_type: FileType,
+    pub old_mode: Mode,
+    pub new_mode: Mode,
+}
+
+/// The list of the mode changes a [`RecursiveChmod`] would do,
+/// computed without modifying anything.
+///
+/// Entries whose mode wouldn't change aren't in the plan.
+///
+/// When the plan is executed, the mode of every entry is checked
+/// before being changed: if it's not the one recorded as `old_mode`,
+/// the entry is left untouched and a [`TreeError::Conflict`] is reported.
+///
+/// ```no_run
+/// use umask::*;
+///
+/// let chmod = RecursiveChmod::new(ModeChange::parse("o-rwx").unwrap());
+/// let plan = chmod.plan("/srv/app");
+/// print!("{}", plan); // one line per change, like "rwxr-xr-x -> rwxr-x---  /srv/app/bin"
+/// let report = plan.execute();
+/// ```
+#[derive(Debug, Default)]
+pub struct ChmodPlan {
+    pub changes: Vec<PlannedChange>,
+    /// Errors met while reading the tree
+    pub errors: Vec<TreeError>,
+    follow_symlinks: bool,
+}
+
+impl RecursiveChmod {
+    /// Compute the changes this chmod would do, without applying them
+    pub fn plan<P: AsRef<Path>>(&self, root: P) -> ChmodPlan {
+        let umask = Umask::current().mode();
+        let mut plan = ChmodPlan {
+            follow_symlinks: self.is_following_symlinks(),
+            ..Default::default()
+        };
+        for entry in self.walker(root.as_ref()) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    plan.errors.push(e);
+                    continue;
+                }
+            };
+            let file_type = entry.file_type();
+            if file_type == FileType::Symlink {
+                continue;
+            }
+            let old_mode = entry.mode();
+            if let Some(new_mode) = self.rules().apply(old_mode, entry.is_dir(), umask) {
+                if new_mode != old_mode {
+                    plan.changes.push(PlannedChange {
+                        path: entry.path,
+                        file_type,
+                        old_mode,
+                        new_mode,
+                    });
+                }
+            }
+        }
+        plan
+    }
+}
+
+impl ChmodPlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+    /// Apply the planned changes, skipping the entries whose
+    /// file type or mode isn't the one seen when planning
+    pub fn execute(&self) -> ChmodReport {
+        let mut report = ChmodReport::default();
+        for change in &self.changes {
+            report.handled += 1;
+            let expected = FullMode::new(change.file_type, change.old_mode);
+            let metadata = if self.follow_symlinks {
+                fs::metadata(&change.path)
+            } else {
+                fs::symlink_metadata(&change.path)
+            };
+            let found = match metadata {
+                Ok(metadata) => FullMode::from(&metadata),
+                Err(source) => {
+                    report.errors.push(TreeError::Read {
+                        path: change.path.clone(),
+                        source,
+                    });
+                    continue;
+                }
+            };
+            if found != expected {
+                report.errors.push(TreeError::Conflict {
+                    path: change.path.clone(),
+                    expected,
+                    found,
+                });
+                continue;
+            }
+            match change.new_mode.apply_to(&change.path) {
+                Ok(()) => report.changed += 1,
+                Err(e) => report.errors.push(e.into()),
+            }
+        }
+        report
+    }
+}
+
+impl Display for PlannedChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {}  {}",
+            self.old_mode,
+            self.new_mode,
+            self.path.display()
+        )
+    }
+}
+
+impl Display for ChmodPlan {
+    /// Formats the plan with one change per line
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_plan() {
+    let root = TestDir::new("plan");
+    for (name, mode) in [("a", 0o644), ("b", 0o640), ("c", 0o666)] {
+        fs::write(root.join(name), "").unwrap();
+        Mode::from(mode).apply_to(root.join(name)).unwrap();
+    }
+    Mode::from(0o750).apply_to(&root).unwrap();
+    let chmod = RecursiveChmod::new(ModeChange::parse("o-rwx").unwrap());
+    let plan = chmod.plan(&root);
+    assert!(plan.errors.is_empty());
+    let changed: Vec<_> = plan
+        .changes
+        .iter()
+        .map(|c| c.path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(changed, vec!["a", "c"]);
+    assert_eq!(
+        plan.changes[0].to_string(),
+        format!("rw-r--r-- -> rw-r-----  {}", root.join("a").display())
+    );
+    // nothing was changed yet
+    assert_eq!(
+        FullMode::try_from(&root.join("a")).unwrap().mode,
+        Mode::from(0o644)
+    );
+    // "c" is modified between the plan and its execution
+    Mode::from(0o664).apply_to(root.join("c")).unwrap();
+    let report = plan.execute();
+    assert_eq!(report.changed, 1);
+    assert!(matches!(report.errors[..], [TreeError::Conflict { .. }]));
+    assert_eq!(
+        FullMode::try_from(&root.join("a")).unwrap().mode,
+        Mode::from(0o640)
+    );
+    assert_eq!(
+        FullMode::try_from(&root.join("c")).unwrap().mode,
+        Mode::from(0o664)
+    );
+}
@@ -0,0 +1,579 @@
+use {
+    crate::*,
+    glob::{MatchOptions, Pattern},
+    serde::Deserialize,
+    std::{
+        fmt::{self, Display, Formatter},
+        fs, io,
+        path::{Path, PathBuf},
+    },
+    thiserror::Error,
+};
+
+/// An error met while loading a [`Policy`] or resolving its owners
+#[derive(Debug, Error)]
+pub enum PolicyError {
+    #[error("reading {path:?} failed: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("unknown policy format for {0:?} (expected a .toml, .yaml or .yml file)")]
+    UnknownFormat(PathBuf),
+    #[error("invalid TOML policy: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("invalid YAML policy: {0}")]
+    Yaml(#[from] serde_yaml_ng::Error),
+    #[error("invalid pattern {pattern:?}: {source}")]
+    InvalidPattern {
+        pattern: String,
+        source: glob::PatternError,
+    },
+    #[error("invalid {field} for {pattern:?}: {source}")]
+    InvalidMode {
+        pattern: String,
+        field: &'static str,
+        source: ParseAnyError,
+    },
+    #[error("invalid {field} for {pattern:?}: modes must be quoted strings like \"0644\", not numbers (found {value})")]
+    NumericMode {
+        pattern: String,
+        field: &'static str,
+        value: i64,
+    },
+    #[error("unknown user {0:?}")]
+    UnknownUser(String),
+    #[error("unknown group {0:?}")]
+    UnknownGroup(String),
+}
+
+/// A user or a group, given by id or by name
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(untagged)]
+pub enum IdSpec {
+    Id(u32),
+    Name(String),
+}
+
+impl IdSpec {
+    /// Return the uid, resolving a user name with the database
+    pub fn uid(&self, db: &IdentityDb) -> Result<u32, PolicyError> {
+        match self {
+            Self::Id(uid) => Ok(*uid),
+            Self::Name(name) => db
+                .uid(name)
+                .ok_or_else(|| PolicyError::UnknownUser(name.clone())),
+        }
+    }
+    /// Return the gid, resolving a group name with the database
+    pub fn gid(&self, db: &IdentityDb) -> Result<u32, PolicyError> {
+        match self {
+            Self::Id(gid) => Ok(*gid),
+            Self::Name(name) => db
+                .gid(name)
+                .ok_or_else(|| PolicyError::UnknownGroup(name.clone())),
+        }
+    }
+}
+
+impl Display for IdSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Id(id) => write!(f, "{}", id),
+            Self::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+/// A mode as written in a policy file: a string in any notation
+/// understood by [`Mode::parse_any`]
+///
+/// Numbers are kept only to be rejected: the parsers give their decimal
+/// value, so `0o644`, `644` and `420` couldn't be told apart.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawMode {
+    Number(i64),
+    Text(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    path: String,
+    mode: Option<RawMode>,
+    max: Option<RawMode>,
+    forbid: Option<RawMode>,
+    owner: Option<IdSpec>,
+    group: Option<IdSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPolicy {
+    #[serde(default)]
+    rules: Vec<RawRule>,
+}
+
+/// The permissions required for the paths matching a glob pattern
+#[derive(Debug, Clone)]
+pub struct PolicyRule {
+    /// The glob pattern, relative to the root of the checked tree
+    pub pattern: String,
+    glob: Pattern,
+    /// The exact mode required
+    pub mode: Option<Mode>,
+    /// The maximal mode allowed: no bit outside of it may be set
+    pub max: Option<Mode>,
+    /// Bits which must not be set
+    pub forbidden: Option<Mode>,
+    pub owner: Option<IdSpec>,
+    pub group: Option<IdSpec>,
+}
+
+impl PolicyRule {
+    /// Tell whether the rule applies to the path, relative to the
+    /// root of the tree (`*` doesn't match `/`, `**` does)
+    pub fn matches<P: AsRef<Path>>(&self, relative_path: P) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        self.glob.matches_path_with(relative_path.as_ref(), options)
+    }
+    fn from_raw(raw: RawRule) -> Result<Self, PolicyError> {
+        let glob = Pattern::new(&raw.path).map_err(|source| PolicyError::InvalidPattern {
+            pattern: raw.path.clone(),
+            source,
+        })?;
+        let pattern = raw.path;
+        let parse = |value: Option<RawMode>, field| {
+            let text = match value {
+                None => return Ok(None),
+                Some(RawMode::Number(value)) => {
+                    return Err(PolicyError::NumericMode {
+                        pattern: pattern.clone(),
+                        field,
+                        value,
+                    });
+                }
+                Some(RawMode::Text(s)) => s,
+            };
+            Mode::parse_any(&text)
+                .map(|(mode, _)| Some(mode))
+                .map_err(|source| PolicyError::InvalidMode {
+                    pattern: pattern.clone(),
+                    field,
+                    source,
+                })
+        };
+        Ok(Self {
+            mode: parse(raw.mode, "mode")?,
+            max: parse(raw.max, "max")?,
+            forbidden: parse(raw.forbid, "forbid")?,
+            pattern,
+            glob,
+            owner: raw.owner,
+            group: raw.group,
+        })
+    }
+}
+
+/// The intended permissions of a tree, as a list of rules
+///
+/// A policy is written in TOML:
+///
+/// ```toml
+/// [[rules]]
+/// path = "bin/*"
+/// mode = "rwxr-xr-x"
+/// owner = "root"
+///
+/// [[rules]]
+/// path = "data/**"
+/// max = "750"
+/// forbid = "u+s,g+s"
+/// group = "app"
+/// ```
+///
+/// or in YAML, with the same fields:
+///
+/// ```yaml
+/// rules:
+///   - path: "bin/*"
+///     mode: "755"
+/// ```
+///
+/// Modes are strings in any notation understood by [`Mode::parse_any`].
+/// Unquoted numbers are rejected, as they would be read as decimal.
+/// All the rules matching a path apply.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub rules: Vec<PolicyRule>,
+}
+
+/// How a path breaks a rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The mode isn't the required one
+    Mode {
+        expected: Mode,
+        found: Mode,
+    },
+    /// Some bits are set outside the maximal mode
+    AboveMax {
+        max: Mode,
+        found: Mode,
+        excess: Mode,
+    },
+    /// Some forbidden bits are set
+    Forbidden {
+        found: Mode,
+        bits: Mode,
+    },
+    Owner {
+        expected: u32,
+        found: u32,
+    },
+    Group {
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mode { expected, found } => {
+                write!(f, "mode is {:o} instead of {:o}", found, expected)
+            }
+            Self::AboveMax { max, found, excess } => write!(
+                f,
+                "mode {:o} exceeds the maximum {:o} by {:o}",
+                found, max, excess
+            ),
+            Self::Forbidden { found, bits } => {
+                write!(f, "mode {:o} has the forbidden bits {:o}", found, bits)
+            }
+            Self::Owner { expected, found } => {
+                write!(f, "owner is uid {} instead of {}", found, expected)
+            }
+            Self::Group { expected, found } => {
+                write!(f, "group is gid {} instead of {}", found, expected)
+            }
+        }
+    }
+}
+
+/// A path breaking a rule of the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub path: PathBuf,
+    /// The pattern of the broken rule
+    pub pattern: String,
+    pub violation: Violation,
+}
+
+impl Display for PolicyViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}: {} (rule {:?})",
+            self.path, self.violation, self.pattern
+        )
+    }
+}
+
+/// The result of checking a tree against a policy
+#[derive(Debug, Default)]
+pub struct PolicyReport {
+    pub violations: Vec<PolicyViolation>,
+    pub errors: Vec<TreeError>,
+}
+
+impl PolicyReport {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty() && self.errors.is_empty()
+    }
+}
+
+impl Policy {
+    pub fn from_toml(s: &str) -> Result<Self, PolicyError> {
+        let raw: RawPolicy = toml::from_str(s)?;
+        Self::from_raw(raw)
+    }
+    pub fn from_yaml(s: &str) -> Result<Self, PolicyError> {
+        let raw: RawPolicy = serde_yaml_ng::from_str(s)?;
+        Self::from_raw(raw)
+    }
+    /// Read a policy file, whose format is given by its extension
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PolicyError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|source| PolicyError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&content),
+            Some("yaml") | Some("yml") => Self::from_yaml(&content),
+            _ => Err(PolicyError::UnknownFormat(path.to_path_buf())),
+        }
+    }
+    fn from_raw(raw: RawPolicy) -> Result<Self, PolicyError> {
+        let rules = raw
+            .rules
+            .into_iter()
+            .map(PolicyRule::from_raw)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+    /// Return the rules applying to the path, relative to the root of the tree
+    pub fn rules_for<'p>(
+        &'p self,
+        relative_path: &'p Path,
+    ) -> impl Iterator<Item = &'p PolicyRule> {
+        self.rules
+            .iter()
+            .filter(move |rule| rule.matches(relative_path))
+    }
+    /// Return the closest mode to the given one which complies with
+    /// the rules applying to the path: the required mode replaces it,
+    /// then the bits above the maximum and the forbidden bits are removed
+    pub fn target_mode(&self, relative_path: &Path, mode: Mode) -> Mode {
+        let mut target = mode;
+        for rule in self.rules_for(relative_path) {
+            if let Some(required) = rule.mode {
+                target = required;
+            }
+            if let Some(max) = rule.max {
+                target = target.intersection(max);
+            }
+            if let Some(forbidden) = rule.forbidden {
+                target = target.without(forbidden);
+            }
+        }
+        target
+    }
+    /// Return how the entry breaks the rule, owner and group names
+    /// being resolved with the database
+    pub fn check_entry(
+        rule: &PolicyRule,
+        file: &FileStat,
+        db: &IdentityDb,
+    ) -> Result<Vec<Violation>, PolicyError> {
+        let found = file.mode;
+        let mut violations = Vec::new();
+        if let Some(expected) = rule.mode {
+            if found != expected {
+                violations.push(Violation::Mode { expected, found });
+            }
+        }
+        if let Some(max) = rule.max {
+            let excess = found.without(max);
+            if excess != Mode::new() {
+                violations.push(Violation::AboveMax { max, found, excess });
+            }
+        }
+        if let Some(forbidden) = rule.forbidden {
+            let bits = found.intersection(forbidden);
+            if bits != Mode::new() {
+                violations.push(Violation::Forbidden { found, bits });
+            }
+        }
+        if let Some(owner) = &rule.owner {
+            let expected = owner.uid(db)?;
+            if file.uid != expected {
+                violations.push(Violation::Owner {
+                    expected,
+                    found: file.uid,
+                });
+            }
+        }
+        if let Some(group) = &rule.group {
+            let expected = group.gid(db)?;
+            if file.gid != expected {
+                violations.push(Violation::Group {
+                    expected,
+                    found: file.gid,
+                });
+            }
+        }
+        Ok(violations)
+    }
+    /// Check all the entries of the tree (symbolic links aren't followed
+    /// nor checked), patterns being relative to the root.
+    ///
+    /// An error is returned, before the tree is read, when an owner or
+    /// group name of a rule isn't in the database.
+    #[cfg(unix)]
+    pub fn check<P: AsRef<Path>>(
+        &self,
+        root: P,
+        db: &IdentityDb,
+    ) -> Result<PolicyReport, PolicyError> {
+        for rule in &self.rules {
+            if let Some(owner) = &rule.owner {
+                owner.uid(db)?;
+            }
+            if let Some(group) = &rule.group {
+                group.gid(db)?;
+            }
+        }
+        let root = root.as_ref();
+        let mut report = PolicyReport::default();
+        for entry in TreeWalker::new(root) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    report.errors.push(e);
+                    continue;
+                }
+            };
+            if entry.file_type() == FileType::Symlink {
+                continue;
+            }
+            let relative_path = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+            let file = FileStat::from(&entry.metadata);
+            for rule in self.rules_for(relative_path) {
+                for violation in Self::check_entry(rule, &file, db)? {
+                    report.violations.push(PolicyViolation {
+                        path: entry.path.clone(),
+                        pattern: rule.pattern.clone(),
+                        violation,
+                    });
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[test]
+fn test_policy_parse() {
+    let toml_policy = Policy::from_toml(
+        r#"
+[[rules]]
+path = "bin/*"
+mode = "rwxr-xr-x"
+owner = "root"
+
+[[rules]]
+path = "data/**"
+max = "750"
+forbid = "u+s,g+s"
+group = 100
+"#,
+    )
+    .unwrap();
+    let yaml_policy = Policy::from_yaml(
+        r#"
+rules:
+  - path: "bin/*"
+    mode: "755"
+    owner: root
+  - path: "data/**"
+    max: "rwxr-x---"
+    forbid: "6000"
+    group: 100
+"#,
+    )
+    .unwrap();
+    for policy in [toml_policy, yaml_policy] {
+        let bin = &policy.rules[0];
+        assert_eq!(bin.mode, Some(Mode::from(0o755)));
+        assert_eq!(bin.owner, Some(IdSpec::Name("root".to_string())));
+        assert!(bin.matches("bin/ls"));
+        assert!(!bin.matches("bin/sub/ls"));
+        let data = &policy.rules[1];
+        assert_eq!(data.max, Some(Mode::from(0o750)));
+        assert_eq!(data.forbidden, Some(Mode::from(0o6000)));
+        assert_eq!(data.group, Some(IdSpec::Id(100)));
+        assert!(data.matches("data/a/b"));
+    }
+    let err = Policy::from_toml("[[rules]]\npath = \"*\"\nmax = \"rwz\"").unwrap_err();
+    assert!(err.to_string().starts_with("invalid max for \"*\": "));
+    assert!(Policy::from_toml("[[rules]]\npath = \"*\"\nmod = 755").is_err());
+    // native octal literals are read as their decimal value, so any number is rejected
+    for err in [
+        Policy::from_toml("[[rules]]\npath = \"*\"\nmode = 0o644").unwrap_err(),
+        Policy::from_yaml("rules:\n  - path: \"*\"\n    mode: 0o644").unwrap_err(),
+    ] {
+        assert!(matches!(
+            err,
+            PolicyError::NumericMode {
+                field: "mode",
+                value: 0o644,
+                ..
+            }
+        ));
+    }
+    assert!(matches!(
+        Policy::from_toml("[[rules]]\npath = \"*\"\nmax = 755"),
+        Err(PolicyError::NumericMode { value: 755, .. })
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_policy_check() {
+    let root = TestDir::new("policy");
+    fs::create_dir_all(root.join("bin")).unwrap();
+    fs::create_dir_all(root.join("data")).unwrap();
+    fs::write(root.join("bin/tool"), "").unwrap();
+    fs::write(root.join("data/secret"), "").unwrap();
+    Mode::from(0o4775).apply_to(root.join("bin/tool")).unwrap();
+    Mode::from(0o640)
+        .apply_to(root.join("data/secret"))
+        .unwrap();
+    Mode::from(0o755).apply_to(root.join("data")).unwrap();
+    let owner = FileStat::read(&root).unwrap();
+    let db = IdentityDb::parse(
+        &format!("me:x:{}:{}::/:/bin/sh\n", owner.uid, owner.gid),
+        &format!("mine:x:{}:\n", owner.gid),
+    )
+    .unwrap();
+    let policy = Policy::from_toml(
+        r#"
+[[rules]]
+path = "bin/*"
+mode = "755"
+owner = "me"
+
+[[rules]]
+path = "data"
+max = "rwxr-x---"
+
+[[rules]]
+path = "**/*"
+forbid = "u+s"
+group = "mine"
+"#,
+    )
+    .unwrap();
+    let report = policy.check(&root, &db).unwrap();
+    assert!(report.errors.is_empty());
+    let found: Vec<String> = report
+        .violations
+        .iter()
+        .map(|v| {
+            let path = v.path.strip_prefix(&root).unwrap().to_string_lossy();
+            format!("{} {}", path, v.violation)
+        })
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            "bin/tool mode is 4775 instead of 755",
+            "bin/tool mode 4775 has the forbidden bits 4000",
+            "data mode 755 exceeds the maximum 750 by 005",
+        ]
+    );
+    let policy = Policy::from_toml("[[rules]]\npath = \"*\"\nowner = \"nobody\"").unwrap();
+    assert!(matches!(
+        policy.check(&root, &db),
+        Err(PolicyError::UnknownUser(_))
+    ));
+    // even when no path matches the rule
+    let policy = Policy::from_toml("[[rules]]\npath = \"none/*\"\ngroup = \"nogroup\"").unwrap();
+    assert!(matches!(
+        policy.check(&root, &db),
+        Err(PolicyError::UnknownGroup(_))
+    ));
+}
@@ -0,0 +1,352 @@
+use {
+    crate::*,
+    std::{
+        fmt::{self, Display, Formatter},
+        fs,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+    thiserror::Error,
+};
+
+/// A mode change bringing an entry into line with a policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub path: PathBuf,
+    pub file_type: FileType,
+    pub old_mode: Mode,
+    pub new_mode: Mode,
+    /// The shortest chmod expression from `old_mode` to `new_mode`, for
+    /// display: the reconciler sets `new_mode` itself
+    pub change: ModeChange,
+}
+
+impl Display for Reconciliation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {}  {}  {}",
+            self.old_mode,
+            self.new_mode,
+            self.change,
+            self.path.display()
+        )
+    }
+}
+
+/// The result of a reconciliation
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    pub dry_run: bool,
+    /// The changes applied, or which would be applied in dry-run
+    pub changes: Vec<Reconciliation>,
+    pub errors: Vec<TreeError>,
+}
+
+impl ReconcileReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+    /// Return the log of the applied changes (empty in dry-run)
+    pub fn rollback_log(&self) -> RollbackLog {
+        if self.dry_run {
+            return RollbackLog::default();
+        }
+        RollbackLog {
+            entries: self
+                .changes
+                .iter()
+                .map(|change| RollbackEntry {
+                    path: change.path.clone(),
+                    before: change.old_mode,
+                    after: change.new_mode,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Applies to a tree the mode changes needed to comply with a policy.
+///
+/// For every entry, the required mode of the matching rules replaces
+/// the current one, then the bits above their maximum and their forbidden
+/// bits are removed, and the resulting mode is set. Each change is
+/// reported with the shortest [`ModeChange`] doing it, as given by
+/// [`ModeChange::shortest_between`].
+///
+/// Owners and groups aren't changed, and symbolic links are neither
+/// followed nor changed.
+///
+/// ```no_run
+/// use umask::*;
+///
+/// let policy = Policy::load("perms.toml").unwrap();
+/// let report = Reconciler::new(&policy).dry_run(true).run("/srv/app");
+/// for change in &report.changes {
+///     println!("{}", change);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Reconciler<'p> {
+    policy: &'p Policy,
+    dry_run: bool,
+}
+
+impl<'p> Reconciler<'p> {
+    pub fn new(policy: &'p Policy) -> Self {
+        Self {
+            policy,
+            dry_run: false,
+        }
+    }
+    /// Compute the changes without applying them
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+    pub fn run<P: AsRef<Path>>(&self, root: P) -> ReconcileReport {
+        let root = root.as_ref();
+        let mut report = ReconcileReport {
+            dry_run: self.dry_run,
+            ..Default::default()
+        };
+        for entry in TreeWalker::new(root) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    report.errors.push(e);
+                    continue;
+                }
+            };
+            let file_type = entry.file_type();
+            if file_type == FileType::Symlink {
+                continue;
+            }
+            let relative_path = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+            let old_mode = entry.mode();
+            let new_mode = self.policy.target_mode(relative_path, old_mode);
+            let change = match ModeChange::shortest_between(old_mode, new_mode) {
+                Some(change) => change,
+                None => continue,
+            };
+            if !self.dry_run {
+                if let Err(e) = new_mode.apply_to(&entry.path) {
+                    report.errors.push(e.into());
+                    continue;
+                }
+            }
+            report.changes.push(Reconciliation {
+                path: entry.path,
+                file_type,
+                old_mode,
+                new_mode,
+                change,
+            });
+        }
+        report
+    }
+}
+
+/// A mode change which can be reverted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollbackEntry {
+    pub path: PathBuf,
+    pub before: Mode,
+    pub after: Mode,
+}
+
+/// Error returned when parsing a [`RollbackLog`]
+#[derive(Debug, Error)]
+#[error("invalid rollback log line {line}: {content:?}")]
+pub struct RollbackLogError {
+    /// Line number, starting at 1
+    pub line: usize,
+    pub content: String,
+}
+
+/// The before and after modes of changed entries, written one
+/// entry per line as `before after path`, with 4 digit octal modes
+/// and the path escaped as in a [`Manifest`]:
+///
+/// ```text
+/// 0775 0750 /srv/app/data
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RollbackLog {
+    pub entries: Vec<RollbackEntry>,
+}
+
+impl RollbackLog {
+    pub fn parse(s: &str) -> Result<Self, RollbackLogError> {
+        let mut entries = Vec::new();
+        for (idx, line) in s.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let mut parts = line.splitn(3, ' ');
+            let mut mode = || parts.next().and_then(|s| Mode::parse_octal(s).ok());
+            let (before, after) = (mode(), mode());
+            match (before, after, parts.next().and_then(unescape_path)) {
+                (Some(before), Some(after), Some(path)) => {
+                    entries.push(RollbackEntry {
+                        path,
+                        before,
+                        after,
+                    });
+                }
+                _ => {
+                    return Err(RollbackLogError {
+                        line: idx + 1,
+                        content: line.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(Self { entries })
+    }
+    /// Restore the modes of before the changes, in reverse order.
+    ///
+    /// Entries whose mode isn't the one set by the change are
+    /// left untouched and reported as [`TreeError::Conflict`].
+    pub fn rollback(&self) -> ChmodReport {
+        let mut report = ChmodReport::default();
+        for entry in self.entries.iter().rev() {
+            report.handled += 1;
+            let found = match fs::symlink_metadata(&entry.path) {
+                Ok(metadata) => FullMode::from(&metadata),
+                Err(source) => {
+                    report.errors.push(TreeError::Read {
+                        path: entry.path.clone(),
+                        source,
+                    });
+                    continue;
+                }
+            };
+            if found.mode != entry.after {
+                report.errors.push(TreeError::Conflict {
+                    path: entry.path.clone(),
+                    expected: FullMode::new(found.file_type, entry.after),
+                    found,
+                });
+                continue;
+            }
+            match entry.before.apply_to(&entry.path) {
+                Ok(()) => report.changed += 1,
+                Err(e) => report.errors.push(e.into()),
+            }
+        }
+        report
+    }
+}
+
+impl FromStr for RollbackLog {
+    type Err = RollbackLogError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for RollbackLog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            write!(
+                f,
+                "{:04o} {:04o} ",
+                u32::from(entry.before),
+                u32::from(entry.after),
+            )?;
+            escape_path(&entry.path, f)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_reconcile() {
+    let root = TestDir::new("reconcile");
+    fs::create_dir_all(root.join("bin")).unwrap();
+    fs::create_dir_all(root.join("data")).unwrap();
+    fs::write(root.join("bin/tool"), "").unwrap();
+    fs::write(root.join("data/file one"), "").unwrap();
+    let modes = [
+        ("bin/tool", 0o4777),
+        ("data", 0o775),
+        ("data/file one", 0o666),
+    ];
+    for (path, mode) in modes {
+        Mode::from(mode).apply_to(root.join(path)).unwrap();
+    }
+    let mode_of = |path: &str| u32::from(FullMode::try_from(&root.join(path)).unwrap().mode);
+    let policy = Policy::from_toml(
+        r#"
+[[rules]]
+path = "bin/*"
+max = "755"
+forbid = "u+s"
+
+[[rules]]
+path = "data"
+mode = "750"
+
+[[rules]]
+path = "data/*"
+max = "640"
+"#,
+    )
+    .unwrap();
+    let report = Reconciler::new(&policy).dry_run(true).run(&root);
+    assert!(report.is_ok());
+    let changes: Vec<String> = report
+        .changes
+        .iter()
+        .map(|c| c.change.to_string())
+        .collect();
+    assert_eq!(changes, vec!["00755", "750", "640"]);
+    assert!(report.rollback_log().entries.is_empty());
+    assert_eq!(mode_of("bin/tool"), 0o4777);
+
+    let report = Reconciler::new(&policy).run(&root);
+    assert!(report.is_ok());
+    assert_eq!(mode_of("bin/tool"), 0o755);
+    assert_eq!(mode_of("data"), 0o750);
+    assert_eq!(mode_of("data/file one"), 0o640);
+    assert!(Reconciler::new(&policy).run(&root).changes.is_empty());
+
+    let log = report.rollback_log().to_string();
+    assert!(log.starts_with(&format!("4777 0755 {}\n", root.join("bin/tool").display())));
+    let log: RollbackLog = log.parse().unwrap();
+    // a change made after the reconciliation isn't overwritten
+    Mode::from(0o700).apply_to(root.join("bin/tool")).unwrap();
+    let rollback = log.rollback();
+    assert_eq!(rollback.changed, 2);
+    assert!(matches!(rollback.errors[..], [TreeError::Conflict { .. }]));
+    assert_eq!(mode_of("data"), 0o775);
+    assert_eq!(mode_of("data/file one"), 0o666);
+    assert!(RollbackLog::parse("0644 /missing/mode").is_err());
+}
+
+#[test]
+fn test_rollback_log_escaping() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+    let log = RollbackLog {
+        entries: vec![
+            RollbackEntry {
+                path: PathBuf::from(OsStr::from_bytes(b"/srv/bad\xff\nname")),
+                before: Mode::from(0o644),
+                after: Mode::from(0o600),
+            },
+            RollbackEntry {
+                path: "/srv/file one".into(),
+                before: Mode::from(0o4755),
+                after: Mode::from(0o755),
+            },
+        ],
+    };
+    let text = log.to_string();
+    assert_eq!(
+        text,
+        "0644 0600 /srv/bad\\xff\\x0aname\n4755 0755 /srv/file one\n"
+    );
+    assert_eq!(text.parse::<RollbackLog>().unwrap(), log);
+}
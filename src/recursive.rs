@@ -0,0 +1,302 @@
+use {
+    crate::*,
+    std::{
+        fmt::{self, Display, Formatter, Write},
+        path::Path,
+    },
+};
+
+/// The changes to apply to directories and to other files
+/// (regular files, but also fifos, sockets or devices).
+///
+/// ```
+/// use umask::*;
+///
+/// let rules = ChmodRules::parse("D755,F644").unwrap();
+/// let umask = Mode::from(0o022);
+/// assert_eq!(rules.apply(Mode::from(0o700), true, umask), Some(Mode::from(0o755)));
+/// assert_eq!(rules.apply(Mode::from(0o700), false, umask), Some(Mode::from(0o644)));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChmodRules {
+    pub dirs: Option<ModeChange>,
+    pub files: Option<ModeChange>,
+}
+
+impl ChmodRules {
+    /// Rules applying the same change to all entries
+    pub fn all(change: ModeChange) -> Self {
+        Self {
+            dirs: Some(change.clone()),
+            files: Some(change),
+        }
+    }
+    /// Parse rules in the syntax of rsync's `--chmod` option: comma
+    /// separated chmod clauses, those prefixed with `D` only applying
+    /// to directories and those prefixed with `F` only applying to
+    /// other files (e.g. `D755,F644` or `Dg+s,ug+w,Fo-w`).
+    pub fn parse<T: AsRef<str>>(s: T) -> Result<Self, ParseError> {
+        let s = s.as_ref();
+        if s.is_empty() {
+            return Err(ParseError::NotEnoughInput);
+        }
+        let mut rules = Self::default();
+        // positions are counted in chars, like in ModeChange::parse
+        let mut offset = 0;
+        for item in s.split(',') {
+            let (for_dirs, for_files, clause) = match item.strip_prefix('D') {
+                Some(clause) => (true, false, clause),
+                None => match item.strip_prefix('F') {
+                    Some(clause) => (false, true, clause),
+                    None => (true, true, item),
+                },
+            };
+            let clause_offset = offset + item.chars().count() - clause.chars().count();
+            let change = ModeChange::parse(clause).map_err(|e| match e {
+                ParseError::InvalidChar(c, pos) => ParseError::InvalidChar(c, pos + clause_offset),
+                ParseError::InvalidOctalDigit(c, pos) => {
+                    ParseError::InvalidOctalDigit(c, pos + clause_offset)
+                }
+                e => e,
+            })?;
+            if for_dirs {
+                rules.dirs = Some(rules.dirs.unwrap_or_default().then(&change));
+            }
+            if for_files {
+                rules.files = Some(rules.files.unwrap_or_default().then(&change));
+            }
+            offset += item.chars().count() + 1;
+        }
+        Ok(rules)
+    }
+    /// Return the new mode of an entry, or `None` when
+    /// there's no rule for this kind of entry
+    pub fn apply(&self, mode: Mode, is_dir: bool, umask: Mode) -> Option<Mode> {
+        let change = if is_dir { &self.dirs } else { &self.files };
+        change
+            .as_ref()
+            .map(|change| change.apply_with_umask(mode, is_dir, umask))
+    }
+}
+
+impl From<ModeChange> for ChmodRules {
+    fn from(change: ModeChange) -> Self {
+        Self::all(change)
+    }
+}
+
+impl std::str::FromStr for ChmodRules {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for ChmodRules {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let (Some(dirs), Some(files)) = (&self.dirs, &self.files) {
+            if dirs == files {
+                return write!(f, "{}", dirs);
+            }
+        }
+        let mut first = true;
+        for (prefix, change) in [('D', &self.dirs), ('F', &self.files)] {
+            let change = match change {
+                Some(change) => change.to_string(),
+                None => continue,
+            };
+            for item in change.split(',') {
+                if !first {
+                    f.write_char(',')?;
+                }
+                write!(f, "{}{}", prefix, item)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What's given to the progress callback of [`RecursiveChmod`]
+/// for every entry which isn't skipped
+#[derive(Debug, Clone, Copy)]
+pub struct ChmodProgress<'p> {
+    pub path: &'p Path,
+    pub old_mode: Mode,
+    pub new_mode: Mode,
+    /// Number of entries handled so far, including this one
+    pub count: usize,
+}
+
+impl ChmodProgress<'_> {
+    pub fn is_change(&self) -> bool {
+        self.old_mode != self.new_mode
+    }
+}
+
+/// The result of a [`RecursiveChmod`] run
+#[derive(Debug, Default)]
+pub struct ChmodReport {
+    /// Number of entries whose new mode was computed
+    pub handled: usize,
+    /// Number of entries whose mode was changed
+    pub changed: usize,
+    /// Number of symbolic links which weren't followed,
+    /// and of entries without applying rule
+    pub skipped: usize,
+    pub errors: Vec<TreeError>,
+}
+
+impl ChmodReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// A recursive chmod, like `chmod -R`.
+///
+/// Errors don't stop the run, they're collected in the report.
+///
+/// ```no_run
+/// use umask::*;
+///
+/// let report = RecursiveChmod::new(ChmodRules::parse("D755,F644").unwrap())
+///     .one_file_system(true)
+///     .run("/srv/www");
+/// for error in &report.errors {
+///     eprintln!("{}", error);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecursiveChmod {
+    rules: ChmodRules,
+    follow_symlinks: bool,
+    one_file_system: bool,
+}
+
+impl RecursiveChmod {
+    pub fn new<R: Into<ChmodRules>>(rules: R) -> Self {
+        Self {
+            rules: rules.into(),
+            follow_symlinks: false,
+            one_file_system: false,
+        }
+    }
+    /// Follow symbolic links instead of skipping them (default: false)
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+    /// Don't go into directories on other file systems
+    /// than the one of the root (default: false)
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.one_file_system = one_file_system;
+        self
+    }
+    pub fn rules(&self) -> &ChmodRules {
+        &self.rules
+    }
+    pub fn is_following_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+    pub(crate) fn walker(&self, root: &Path) -> TreeWalker {
+        TreeWalker::new(root)
+            .follow_symlinks(self.follow_symlinks)
+            .one_file_system(self.one_file_system)
+    }
+    /// Change the modes of the root and all its descendants
+    pub fn run<P: AsRef<Path>>(&self, root: P) -> ChmodReport {
+        self.run_with_progress(root, |_| {})
+    }
+    /// Change the modes of the root and all its descendants,
+    /// calling `progress` for every entry
+    pub fn run_with_progress<P, F>(&self, root: P, mut progress: F) -> ChmodReport
+    where
+        P: AsRef<Path>,
+        F: FnMut(&ChmodProgress),
+    {
+        let umask = Umask::current().mode();
+        let mut report = ChmodReport::default();
+        for entry in self.walker(root.as_ref()) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    report.errors.push(e);
+                    continue;
+                }
+            };
+            if entry.file_type() == FileType::Symlink {
+                report.skipped += 1;
+                continue;
+            }
+            let old_mode = entry.mode();
+            let new_mode = match self.rules.apply(old_mode, entry.is_dir(), umask) {
+                Some(new_mode) => new_mode,
+                None => {
+                    report.skipped += 1;
+                    continue;
+                }
+            };
+            report.handled += 1;
+            if new_mode != old_mode {
+                match new_mode.apply_to(&entry.path) {
+                    Ok(()) => report.changed += 1,
+                    Err(e) => report.errors.push(e.into()),
+                }
+            }
+            progress(&ChmodProgress {
+                path: &entry.path,
+                old_mode,
+                new_mode,
+                count: report.handled,
+            });
+        }
+        report
+    }
+}
+
+#[test]
+fn test_chmod_rules() {
+    let rules = ChmodRules::parse("Dg+s,ug+w,Fo-w").unwrap();
+    assert_eq!(rules.to_string(), "Dg+s,Dug+w,Fug+w,Fo-w");
+    assert_eq!(ChmodRules::parse(rules.to_string()).unwrap(), rules);
+    let umask = Mode::from(0o022);
+    assert_eq!(
+        rules.apply(Mode::from(0o755), true, umask),
+        Some(Mode::from(0o2775))
+    );
+    assert_eq!(
+        rules.apply(Mode::from(0o646), false, umask),
+        Some(Mode::from(0o664))
+    );
+    assert_eq!(ChmodRules::parse("D755").unwrap().files, None);
+    assert!(matches!(
+        ChmodRules::parse("D755,Fu+k"),
+        Err(ParseError::InvalidChar('k', 8))
+    ));
+    assert!(matches!(
+        ChmodRules::parse("D755,F\u{e9}"),
+        Err(ParseError::InvalidChar('\u{e9}', 6))
+    ));
+}
+
+#[test]
+fn test_recursive_chmod() {
+    use std::fs;
+    let root = TestDir::new("recursive");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("sub/file"), "").unwrap();
+    Mode::from(0o700).apply_to(&root).unwrap();
+    Mode::from(0o600).apply_to(root.join("sub/file")).unwrap();
+    std::os::unix::fs::symlink("sub/file", root.join("link")).unwrap();
+    let mut paths = Vec::new();
+    let report = RecursiveChmod::new(ChmodRules::parse("D755,F644").unwrap())
+        .run_with_progress(&root, |p| paths.push(p.path.to_path_buf()));
+    assert!(report.is_ok());
+    assert_eq!(report.skipped, 1);
+    assert_eq!(paths.len(), 3);
+    let mode = |path: &str| FullMode::try_from(&root.join(path)).unwrap().mode;
+    assert_eq!(mode(""), Mode::from(0o755));
+    assert_eq!(mode("sub"), Mode::from(0o755));
+    assert_eq!(mode("sub/file"), Mode::from(0o644));
+}
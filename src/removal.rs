@@ -0,0 +1,295 @@
+use {
+    crate::*,
+    std::fmt::{self, Display, Formatter},
+};
+
+/// Why removing an entry from a directory is allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RemovalGrant {
+    /// The directory is writable and searchable and isn't sticky
+    NotSticky,
+    /// The directory is sticky but the identity owns the entry
+    OwnsEntry,
+    /// The directory is sticky but the identity owns the directory
+    OwnsDirectory,
+    /// The directory is sticky but the process has CAP_FOWNER
+    Fowner,
+    /// The destination of a rename is writable and searchable (its
+    /// sticky bit doesn't matter when no entry is replaced)
+    Writable,
+}
+
+impl Display for RemovalGrant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::NotSticky => "the directory is writable and searchable, without sticky bit",
+            Self::OwnsEntry => "the directory is sticky, but the entry is owned",
+            Self::OwnsDirectory => "the directory is sticky, but it is owned",
+            Self::Fowner => "the directory is sticky, but CAP_FOWNER bypasses the restriction",
+            Self::Writable => "the destination is writable and searchable",
+        })
+    }
+}
+
+/// Why removing an entry from a directory is denied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RemovalDenial {
+    /// The directory isn't both writable and searchable
+    DirectoryAccess(Access),
+    /// The directory is sticky and the identity owns neither the
+    /// entry nor the directory
+    Sticky {
+        uid: u32,
+        entry_uid: u32,
+        dir_uid: u32,
+    },
+    /// A directory moved to another parent must be writable,
+    /// as its `..` entry is changed
+    MovedDirectory(Access),
+}
+
+impl Display for RemovalDenial {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DirectoryAccess(access) => {
+                write!(f, "the directory isn't writable and searchable ({})", access)
+            }
+            Self::Sticky {
+                uid,
+                entry_uid,
+                dir_uid,
+            } => write!(
+                f,
+                "the directory is sticky and uid {} owns neither the entry (uid {}) nor the directory (uid {})",
+                uid, entry_uid, dir_uid,
+            ),
+            Self::MovedDirectory(access) => write!(
+                f,
+                "a directory moved to another parent must be writable ({})",
+                access
+            ),
+        }
+    }
+}
+
+/// The answer to "can this entry be unlinked or renamed?",
+/// with its explanation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemovalVerdict {
+    /// Allowed, with the grant of every directory involved
+    /// (the source directory first for a rename)
+    Allowed(Vec<RemovalGrant>),
+    Denied(RemovalDenial),
+}
+
+impl RemovalVerdict {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Self::Allowed(_))
+    }
+}
+
+impl Display for RemovalVerdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Allowed(grants) => {
+                f.write_str("allowed: ")?;
+                for (i, grant) in grants.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("; ")?;
+                    }
+                    write!(f, "{}", grant)?;
+                }
+                Ok(())
+            }
+            Self::Denied(denial) => write!(f, "denied: {}", denial),
+        }
+    }
+}
+
+/// Tell whether the identity can unlink the entry from its parent
+/// directory, root being considered as having all capabilities.
+///
+/// See [`AccessEvaluator::can_unlink`].
+pub fn can_unlink(identity: &Identity, parent: &FileStat, entry: &FileStat) -> RemovalVerdict {
+    AccessEvaluator::privilege_aware(identity.clone()).can_unlink(parent, entry)
+}
+
+/// Tell whether the identity can rename the entry, root being
+/// considered as having all capabilities.
+///
+/// See [`AccessEvaluator::can_rename`].
+pub fn can_rename(
+    identity: &Identity,
+    parent: &FileStat,
+    entry: &FileStat,
+    destination: Option<&FileStat>,
+    replaced: Option<&FileStat>,
+) -> RemovalVerdict {
+    AccessEvaluator::privilege_aware(identity.clone()).can_rename(
+        parent,
+        entry,
+        destination,
+        replaced,
+    )
+}
+
+impl AccessEvaluator {
+    /// Check the rules of removing an entry from a directory
+    fn check_removal(
+        &self,
+        dir: &FileStat,
+        entry: &FileStat,
+    ) -> Result<RemovalGrant, RemovalDenial> {
+        let dir_access = self.evaluate(dir);
+        if !dir_access.allows(WRITE | EXEC) {
+            return Err(RemovalDenial::DirectoryAccess(dir_access));
+        }
+        let uid = self.identity().uid;
+        if !dir.mode.has_extra(STICKY) {
+            Ok(RemovalGrant::NotSticky)
+        } else if uid == entry.uid {
+            Ok(RemovalGrant::OwnsEntry)
+        } else if uid == dir.uid {
+            Ok(RemovalGrant::OwnsDirectory)
+        } else if self.capabilities().fowner {
+            Ok(RemovalGrant::Fowner)
+        } else {
+            Err(RemovalDenial::Sticky {
+                uid,
+                entry_uid: entry.uid,
+                dir_uid: dir.uid,
+            })
+        }
+    }
+    /// Tell whether the entry can be unlinked (or, for a directory,
+    /// removed once empty) from its parent directory: the parent must
+    /// be writable and searchable and, when it has the sticky bit, the
+    /// identity must own the entry or the directory, or have CAP_FOWNER.
+    ///
+    /// ```
+    /// use umask::*;
+    ///
+    /// let tmp = FileStat::new(FileType::Directory, Mode::from(0o1777), 0, 0);
+    /// let file = FileStat::new(FileType::Regular, Mode::from(0o644), 1001, 1001);
+    /// let evaluator = AccessEvaluator::new(Identity::new(1000, 1000));
+    /// assert!(!evaluator.can_unlink(&tmp, &file).is_allowed());
+    /// ```
+    pub fn can_unlink(&self, parent: &FileStat, entry: &FileStat) -> RemovalVerdict {
+        match self.check_removal(parent, entry) {
+            Ok(grant) => RemovalVerdict::Allowed(vec![grant]),
+            Err(denial) => RemovalVerdict::Denied(denial),
+        }
+    }
+    /// Tell whether the entry can be renamed.
+    ///
+    /// `destination` is the directory the entry is moved to, `None`
+    /// when it stays in the same directory, and `replaced` is the
+    /// existing entry which would be replaced, if any.
+    ///
+    /// The entry must be removable from its parent, the replaced entry
+    /// must be removable from the destination, the destination must be
+    /// writable and searchable and, when a directory is moved to another
+    /// parent, it must be writable itself.
+    pub fn can_rename(
+        &self,
+        parent: &FileStat,
+        entry: &FileStat,
+        destination: Option<&FileStat>,
+        replaced: Option<&FileStat>,
+    ) -> RemovalVerdict {
+        let mut grants = Vec::new();
+        match self.check_removal(parent, entry) {
+            Ok(grant) => grants.push(grant),
+            Err(denial) => return RemovalVerdict::Denied(denial),
+        }
+        let destination_dir = destination.unwrap_or(parent);
+        if let Some(replaced) = replaced {
+            match self.check_removal(destination_dir, replaced) {
+                Ok(grant) => grants.push(grant),
+                Err(denial) => return RemovalVerdict::Denied(denial),
+            }
+        } else if destination.is_some() {
+            let access = self.evaluate(destination_dir);
+            if !access.allows(WRITE | EXEC) {
+                return RemovalVerdict::Denied(RemovalDenial::DirectoryAccess(access));
+            }
+            grants.push(RemovalGrant::Writable);
+        }
+        if destination.is_some() && entry.file_type == FileType::Directory {
+            let access = self.evaluate(entry);
+            if !access.write {
+                return RemovalVerdict::Denied(RemovalDenial::MovedDirectory(access));
+            }
+        }
+        RemovalVerdict::Allowed(grants)
+    }
+}
+
+#[test]
+fn test_can_unlink() {
+    let tmp = FileStat::new(FileType::Directory, Mode::from(0o1777), 0, 0);
+    let own_dir = FileStat::new(FileType::Directory, Mode::from(0o1755), 1000, 1000);
+    let shared = FileStat::new(FileType::Directory, Mode::from(0o775), 0, 100);
+    let theirs = FileStat::new(FileType::Regular, Mode::from(0o666), 1001, 100);
+    let mine = FileStat::new(FileType::Regular, Mode::from(0o600), 1000, 1000);
+    let me = AccessEvaluator::new(Identity::new(1000, 1000));
+    assert_eq!(
+        me.can_unlink(&tmp, &mine),
+        RemovalVerdict::Allowed(vec![RemovalGrant::OwnsEntry])
+    );
+    assert_eq!(
+        me.can_unlink(&tmp, &theirs),
+        RemovalVerdict::Denied(RemovalDenial::Sticky {
+            uid: 1000,
+            entry_uid: 1001,
+            dir_uid: 0
+        })
+    );
+    assert_eq!(
+        me.can_unlink(&own_dir, &theirs),
+        RemovalVerdict::Allowed(vec![RemovalGrant::OwnsDirectory])
+    );
+    // the file being writable doesn't matter, the directory does
+    let verdict = me.can_unlink(&shared, &theirs);
+    assert!(matches!(
+        verdict,
+        RemovalVerdict::Denied(RemovalDenial::DirectoryAccess(_))
+    ));
+    assert!(verdict
+        .to_string()
+        .starts_with("denied: the directory isn't writable"));
+    let member = AccessEvaluator::new(Identity::new(1000, 1000).with_groups(vec![100]));
+    assert!(member.can_unlink(&shared, &theirs).is_allowed());
+    let root = Identity::new(0, 0);
+    let somebody_else = FileStat::new(FileType::Regular, Mode::from(0o600), 1000, 1000);
+    let others_tmp = FileStat::new(FileType::Directory, Mode::from(0o1777), 1001, 0);
+    assert_eq!(
+        can_unlink(&root, &others_tmp, &somebody_else),
+        RemovalVerdict::Allowed(vec![RemovalGrant::Fowner])
+    );
+}
+
+#[test]
+fn test_can_rename() {
+    let home = FileStat::new(FileType::Directory, Mode::from(0o755), 1000, 1000);
+    let tmp = FileStat::new(FileType::Directory, Mode::from(0o1777), 0, 0);
+    let mine = FileStat::new(FileType::Regular, Mode::from(0o600), 1000, 1000);
+    let theirs = FileStat::new(FileType::Regular, Mode::from(0o666), 1001, 1001);
+    let my_dir = FileStat::new(FileType::Directory, Mode::from(0o555), 1000, 1000);
+    let me = AccessEvaluator::new(Identity::new(1000, 1000));
+    assert!(me.can_rename(&home, &mine, None, None).is_allowed());
+    assert_eq!(
+        me.can_rename(&home, &mine, Some(&tmp), None),
+        RemovalVerdict::Allowed(vec![RemovalGrant::NotSticky, RemovalGrant::Writable])
+    );
+    // replacing a file of somebody else in a sticky directory
+    assert!(!me
+        .can_rename(&home, &mine, Some(&tmp), Some(&theirs))
+        .is_allowed());
+    // moving a read-only directory to another parent
+    assert!(me.can_rename(&home, &my_dir, None, None).is_allowed());
+    assert!(matches!(
+        me.can_rename(&home, &my_dir, Some(&tmp), None),
+        RemovalVerdict::Denied(RemovalDenial::MovedDirectory(_))
+    ));
+}
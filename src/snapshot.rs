@@ -0,0 +1,484 @@
+use {
+    crate::*,
+    std::{
+        collections::{HashMap, HashSet},
+        ffi::CString,
+        fmt::{self, Display, Formatter},
+        fs, io,
+        os::unix::{ffi::OsStrExt, fs::MetadataExt},
+        path::{Component, Path, PathBuf},
+        str::FromStr,
+    },
+    thiserror::Error,
+};
+
+/// The recorded state of an entry of a tree
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ManifestEntry {
+    /// The path relative to the root of the tree, `.` for the root
+    pub path: PathBuf,
+    /// The file type and the mode, including the extra permission bits
+    pub full_mode: FullMode,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl ManifestEntry {
+    /// Record an entry met while walking the tree starting at `root`
+    pub(crate) fn new(root: &Path, entry: &TreeEntry) -> Self {
+        let path = match entry.path.strip_prefix(root) {
+            Ok(path) if path.as_os_str().is_empty() => PathBuf::from("."),
+            Ok(path) => path.to_path_buf(),
+            Err(_) => entry.path.clone(),
+        };
+        Self {
+            path,
+            full_mode: entry.full_mode(),
+            uid: entry.metadata.uid(),
+            gid: entry.metadata.gid(),
+        }
+    }
+}
+
+/// Error returned when parsing a [`Manifest`]
+#[derive(Debug, Error)]
+#[error("invalid manifest line {line}: {content:?}")]
+pub struct ManifestError {
+    /// Line number, starting at 1
+    pub line: usize,
+    pub content: String,
+}
+
+/// The file types, modes and owners of all the entries of a tree,
+/// as made by [`snapshot`] and used by [`restore`].
+///
+/// Its text form has one line per entry, in the `ls -l` notation
+/// followed by the uid, the gid and the relative path:
+///
+/// ```text
+/// # umask manifest
+/// drwxr-xr-x 1000 1000 .
+/// drwxrwsr-x 1000 100 shared
+/// -rw-rw-r-- 1000 100 shared/notes.txt
+/// ```
+///
+/// Paths are relative and can't go up with `..`. In paths, backslashes,
+/// control characters and bytes which aren't valid UTF-8 are escaped
+/// as `\\` and `\xHH`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// The result of a [`snapshot`]
+#[derive(Debug, Default)]
+pub struct SnapshotReport {
+    pub manifest: Manifest,
+    /// Errors met while reading the tree, whose entries are missing
+    /// from the manifest
+    pub errors: Vec<TreeError>,
+}
+
+impl SnapshotReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Record the file type, mode, uid and gid of every entry
+/// of the tree, symbolic links not being followed
+///
+/// The entries which can't be read are reported as errors
+/// and the others are still recorded.
+///
+/// ```no_run
+/// use umask::*;
+///
+/// let report = snapshot("/srv/app");
+/// for error in &report.errors {
+///     eprintln!("{}", error);
+/// }
+/// std::fs::write("app.perms", report.manifest.to_string()).unwrap();
+/// // later
+/// let manifest: Manifest = std::fs::read_to_string("app.perms").unwrap().parse().unwrap();
+/// let report = restore(&manifest, "/srv/app");
+/// ```
+pub fn snapshot<P: AsRef<Path>>(root: P) -> SnapshotReport {
+    let root = root.as_ref();
+    let mut report = SnapshotReport::default();
+    for entry in TreeWalker::new(root) {
+        match entry {
+            Ok(entry) => report
+                .manifest
+                .entries
+                .push(ManifestEntry::new(root, &entry)),
+            Err(e) => report.errors.push(e),
+        }
+    }
+    report
+}
+
+/// The result of a [`restore`]
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    /// Number of entries of the manifest which were handled
+    pub handled: usize,
+    /// Number of entries whose mode was changed
+    pub modes_changed: usize,
+    /// Number of entries whose owner or group was changed
+    pub owners_changed: usize,
+    pub errors: Vec<TreeError>,
+}
+
+impl RestoreReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Set back the owners, groups and modes recorded in the manifest,
+/// like `setfacl --restore`.
+///
+/// Directories are first made searchable by their owner, parents first,
+/// so that their content can be reached even when their search bit
+/// was removed. Entries are then restored children first, so that
+/// restoring the mode of a directory doesn't prevent reaching its
+/// content. An entry whose file
+/// type isn't the recorded one is left untouched and reported as a
+/// [`TreeError::Conflict`]. Entries of the tree which aren't in the
+/// manifest are ignored.
+///
+/// Entries whose path is absolute, goes up with `..`, or goes through
+/// something else than a directory (e.g. a directory replaced by a
+/// symbolic link) are left untouched and reported as
+/// [`TreeError::OutOfTree`].
+///
+/// The owner is set before the mode, as changing the owner may clear
+/// the setuid and setgid bits. Changing the owner usually needs privileges.
+pub fn restore<P: AsRef<Path>>(manifest: &Manifest, root: P) -> RestoreReport {
+    let root = root.as_ref();
+    let mut report = RestoreReport::default();
+    let mut entries: Vec<&ManifestEntry> = manifest.entries.iter().collect();
+    entries.sort_by_key(|entry| entry.path.components().count());
+    // the modes of the directories made searchable, before the change
+    let mut opened = HashMap::new();
+    // the directories checked not to lead out of the tree
+    let mut dirs = HashSet::new();
+    for entry in &entries {
+        if entry.full_mode.file_type != FileType::Directory {
+            continue;
+        }
+        // errors are reported by the restoring pass
+        let path = match tree_path(root, &entry.path, &mut dirs) {
+            Ok(path) => path,
+            Err(_) => continue,
+        };
+        if let Ok(found) = FullMode::try_from(&path) {
+            if found.file_type == FileType::Directory
+                && !found.mode.has(USER_EXEC)
+                && (found.mode | USER_EXEC).apply_to(&path).is_ok()
+            {
+                opened.insert(path, found.mode);
+            }
+        }
+    }
+    for entry in entries.into_iter().rev() {
+        report.handled += 1;
+        let path = match tree_path(root, &entry.path, &mut dirs) {
+            Ok(path) => path,
+            Err(e) => {
+                report.errors.push(e);
+                continue;
+            }
+        };
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(source) => {
+                report.errors.push(TreeError::Read { path, source });
+                continue;
+            }
+        };
+        let found = FullMode::from(&metadata);
+        if found.file_type != entry.full_mode.file_type {
+            report.errors.push(TreeError::Conflict {
+                path,
+                expected: entry.full_mode,
+                found,
+            });
+            continue;
+        }
+        let mut chowned = false;
+        if metadata.uid() != entry.uid || metadata.gid() != entry.gid {
+            if let Err(source) = lchown(&path, entry.uid, entry.gid) {
+                report.errors.push(TreeError::Chown {
+                    path,
+                    uid: entry.uid,
+                    gid: entry.gid,
+                    source,
+                });
+                continue;
+            }
+            report.owners_changed += 1;
+            chowned = true;
+        }
+        // the mode of a symbolic link can't be changed
+        if found.file_type == FileType::Symlink {
+            continue;
+        }
+        if chowned || found.mode != entry.full_mode.mode {
+            let initial_mode = opened.get(&path).copied().unwrap_or(found.mode);
+            match entry.full_mode.mode.apply_to(&path) {
+                Ok(()) if chowned || initial_mode != entry.full_mode.mode => {
+                    report.modes_changed += 1;
+                }
+                Ok(()) => {}
+                Err(e) => report.errors.push(e.into()),
+            }
+        }
+    }
+    report
+}
+
+/// Tell whether the path designates an entry of a tree from its root:
+/// `.` for the root, or a relative path without `..`
+fn is_in_tree(relative_path: &Path) -> bool {
+    relative_path == Path::new(".")
+        || relative_path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Join the relative path of an entry to the root, checking the
+/// path can't lead out of the tree. The parents of the entry, which
+/// must all be directories, are added to `dirs` once checked.
+fn tree_path(
+    root: &Path,
+    relative_path: &Path,
+    dirs: &mut HashSet<PathBuf>,
+) -> Result<PathBuf, TreeError> {
+    let path = root.join(relative_path);
+    if !is_in_tree(relative_path) {
+        return Err(TreeError::OutOfTree { path });
+    }
+    let mut parent = root.to_path_buf();
+    let components: Vec<Component> = relative_path.components().collect();
+    for component in &components[..components.len().saturating_sub(1)] {
+        parent.push(component);
+        if dirs.contains(&parent) {
+            continue;
+        }
+        match fs::symlink_metadata(&parent) {
+            Ok(metadata) if metadata.is_dir() => {
+                dirs.insert(parent.clone());
+            }
+            Ok(_) => return Err(TreeError::OutOfTree { path }),
+            Err(source) => {
+                return Err(TreeError::Read {
+                    path: parent,
+                    source,
+                })
+            }
+        }
+    }
+    Ok(path)
+}
+
+fn lchown(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    if unsafe { libc::lchown(c_path.as_ptr(), uid, gid) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+impl Manifest {
+    pub fn parse(s: &str) -> Result<Self, ManifestError> {
+        let mut entries = Vec::new();
+        for (idx, line) in s.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(4, ' ');
+            let full_mode = parts.next().and_then(|s| FullMode::parse(s).ok());
+            let uid = parts.next().and_then(|s| s.parse().ok());
+            let gid = parts.next().and_then(|s| s.parse().ok());
+            let path = parts
+                .next()
+                .and_then(unescape_path)
+                .filter(|path| is_in_tree(path));
+            match (full_mode, uid, gid, path) {
+                (Some(full_mode), Some(uid), Some(gid), Some(path)) => {
+                    entries.push(ManifestEntry {
+                        path,
+                        full_mode,
+                        uid,
+                        gid,
+                    });
+                }
+                _ => {
+                    return Err(ManifestError {
+                        line: idx + 1,
+                        content: line.to_string(),
+                    });
+                }
+            }
+        }
+        Ok(Self { entries })
+    }
+    /// Return the entry with the given relative path
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&ManifestEntry> {
+        let path = path.as_ref();
+        self.entries.iter().find(|entry| entry.path == path)
+    }
+}
+
+impl FromStr for Manifest {
+    type Err = ManifestError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for ManifestEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} ", self.full_mode, self.uid, self.gid)?;
+        escape_path(&self.path, f)
+    }
+}
+
+impl Display for Manifest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# umask manifest")?;
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let root = TestDir::new("snapshot");
+    fs::create_dir_all(root.join("shared")).unwrap();
+    fs::write(root.join("shared/notes"), "").unwrap();
+    fs::write(root.join("odd\\name\n\u{e9}"), "").unwrap();
+    fs::write(root.join(std::ffi::OsStr::from_bytes(b"bad\xff")), "").unwrap();
+    std::os::unix::fs::symlink("shared", root.join("link")).unwrap();
+    let modes = [
+        ("", 0o755),
+        ("shared", 0o2775),
+        ("shared/notes", 0o4664),
+        ("odd\\name\n\u{e9}", 0o600),
+    ];
+    for (path, mode) in modes {
+        Mode::from(mode).apply_to(root.join(path)).unwrap();
+    }
+    let report = snapshot(&root);
+    assert!(report.is_ok());
+    let manifest = report.manifest;
+    let text = manifest.to_string();
+    let owner = FileStat::read(&root).unwrap();
+    assert!(text.contains(&format!(
+        "\ndrwxrwsr-x {} {} shared\n",
+        owner.uid, owner.gid
+    )));
+    assert!(text.contains(" bad\\xff\n"));
+    assert!(text.contains(" odd\\\\name\\x0a\u{e9}\n"));
+    let parsed: Manifest = text.parse().unwrap();
+    assert_eq!(parsed, manifest);
+    assert_eq!(
+        parsed.get("shared/notes").unwrap().full_mode,
+        FullMode::parse("-rwSrw-r--").unwrap()
+    );
+
+    // a bad recursive chmod
+    let report = RecursiveChmod::new(ModeChange::parse("u-w,go=").unwrap()).run(&root);
+    assert!(report.is_ok());
+    let report = restore(&manifest, &root);
+    assert!(report.is_ok(), "{:?}", report.errors);
+    assert_eq!(report.modes_changed, 5);
+    assert_eq!(snapshot(&root).manifest, manifest);
+    assert!(Manifest::parse("-rw-r--r-- 0 0").is_err());
+
+    // directories whose search bit was removed are reached
+    Mode::from(0o600)
+        .apply_to(root.join("shared/notes"))
+        .unwrap();
+    Mode::from(0o664).apply_to(root.join("shared")).unwrap();
+    let report = restore(&manifest, &root);
+    assert!(report.is_ok(), "{:?}", report.errors);
+    assert_eq!(report.modes_changed, 2);
+    assert_eq!(snapshot(&root).manifest, manifest);
+    // and given back their mode when it's the recorded one
+    fs::create_dir(root.join("vault")).unwrap();
+    fs::write(root.join("vault/key"), "").unwrap();
+    Mode::from(0o600).apply_to(root.join("vault")).unwrap();
+    let manifest = snapshot(&root).manifest;
+    Mode::from(0o640).apply_to(root.join("vault/key")).unwrap();
+    let report = restore(&manifest, &root);
+    assert!(report.is_ok(), "{:?}", report.errors);
+    assert_eq!(report.modes_changed, 1);
+    assert_eq!(snapshot(&root).manifest, manifest);
+
+    let report = snapshot(root.join("missing"));
+    assert!(report.manifest.entries.is_empty());
+    assert!(matches!(report.errors[..], [TreeError::Read { .. }]));
+}
+
+#[test]
+fn test_restore_out_of_tree() {
+    use std::os::unix::fs::symlink;
+    let base = TestDir::new("restore-out-of-tree");
+    let (root, outside) = (base.join("root"), base.join("outside"));
+    fs::create_dir_all(root.join("shared")).unwrap();
+    fs::create_dir_all(&outside).unwrap();
+    fs::write(root.join("shared/notes"), "").unwrap();
+    fs::write(outside.join("notes"), "").unwrap();
+    Mode::from(0o600).apply_to(outside.join("notes")).unwrap();
+    let mode_of = |path: &Path| u32::from(FullMode::try_from(path).unwrap().mode);
+
+    assert!(Manifest::parse("-rwxrwxrwx 0 0 ../outside/notes").is_err());
+    assert!(Manifest::parse("-rwxrwxrwx 0 0 shared/../../outside/notes").is_err());
+    assert!(Manifest::parse("-rwxrwxrwx 0 0 /etc/passwd").is_err());
+    assert!(Manifest::parse("-rwxrwxrwx 0 0 ./shared").is_err());
+    assert!(Manifest::parse("drwxrwxrwx 0 0 .").is_ok());
+
+    // paths going up, in a manifest not made by parsing
+    let owner = FileStat::read(&root).unwrap();
+    let entry = |path: &str, full_mode: &str| ManifestEntry {
+        path: path.into(),
+        full_mode: FullMode::parse(full_mode).unwrap(),
+        uid: owner.uid,
+        gid: owner.gid,
+    };
+    let manifest = Manifest {
+        entries: vec![entry("../outside/notes", "-rwxrwxrwx")],
+    };
+    let report = restore(&manifest, &root);
+    assert!(matches!(report.errors[..], [TreeError::OutOfTree { .. }]));
+    assert_eq!(mode_of(&outside.join("notes")), 0o600);
+
+    // a directory replaced by a symbolic link isn't gone through
+    let manifest = Manifest {
+        entries: vec![
+            entry(".", "drwxr-xr-x"),
+            entry("shared", "drwxrwxrwx"),
+            entry("shared/notes", "-rwxrwxrwx"),
+        ],
+    };
+    let outside_mode = mode_of(&outside);
+    fs::remove_dir_all(root.join("shared")).unwrap();
+    symlink(&outside, root.join("shared")).unwrap();
+    let report = restore(&manifest, &root);
+    assert!(matches!(
+        report.errors[..],
+        [TreeError::OutOfTree { .. }, TreeError::Conflict { .. }]
+    ));
+    assert_eq!(
+        report.errors[0].path(),
+        Some(root.join("shared/notes").as_path())
+    );
+    assert_eq!(mode_of(&outside.join("notes")), 0o600);
+    assert_eq!(mode_of(&outside), outside_mode);
+}
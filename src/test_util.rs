@@ -0,0 +1,73 @@
+use {
+    crate::*,
+    std::{
+        fs::{self, Permissions},
+        ops::Deref,
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        sync::RwLockReadGuard,
+    },
+};
+
+/// A directory created for a test, and removed with its content
+/// when dropped, even when the test fails.
+///
+/// It also prevents the umask from being changed by another test
+/// while it exists.
+pub(crate) struct TestDir {
+    path: PathBuf,
+    _umask_lock: RwLockReadGuard<'static, ()>,
+}
+
+impl TestDir {
+    /// Create an empty directory, named after the test, in the
+    /// temporary directory
+    pub(crate) fn new(name: &str) -> Self {
+        let umask_lock = UMASK_LOCK.read().unwrap_or_else(|e| e.into_inner());
+        let path = std::env::temp_dir().join(format!("umask-test-{}-{}", name, std::process::id()));
+        remove(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self {
+            path,
+            _umask_lock: umask_lock,
+        }
+    }
+}
+
+/// Remove a tree, giving back to its directories the permissions
+/// a test may have removed
+fn remove(path: &Path) {
+    fn make_removable(path: &Path) {
+        let is_dir = fs::symlink_metadata(path).is_ok_and(|m| m.is_dir());
+        if !is_dir {
+            return;
+        }
+        let _ = fs::set_permissions(path, Permissions::from_mode(0o700));
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                make_removable(&entry.path());
+            }
+        }
+    }
+    make_removable(path);
+    let _ = fs::remove_dir_all(path);
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        remove(&self.path);
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
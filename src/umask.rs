@@ -0,0 +1,251 @@
+use {
+    crate::*,
+    std::fmt::{self, Display, Formatter, Write},
+};
+
+/// The file mode creation mask of a process: the permission
+/// bits which are removed from the modes of created files.
+///
+/// ```
+/// use umask::*;
+///
+/// let umask = Umask::from(Mode::from(0o022));
+/// assert!(umask.mode().has(GROUP_WRITE));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Umask {
+    mode: Mode,
+}
+
+impl fmt::Debug for Umask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Umask({:04o})", u32::from(self.mode))
+    }
+}
+
+impl Display for Umask {
+    /// Formats the umask as `umask` does (`"0022"`) or, with the
+    /// alternate flag (`{:#}`), as `umask -S` does (`"u=rwx,g=rx,o=rx"`),
+    /// that is by listing the permissions which are *not* masked.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if !f.alternate() {
+            return write!(f, "{:04o}", u32::from(self.mode));
+        }
+        let allowed = !self.mode;
+        for (name, read, write, exec) in [
+            ("u=", USER_READ, USER_WRITE, USER_EXEC),
+            (",g=", GROUP_READ, GROUP_WRITE, GROUP_EXEC),
+            (",o=", OTHERS_READ, OTHERS_WRITE, OTHERS_EXEC),
+        ] {
+            f.write_str(name)?;
+            if allowed.has(read) {
+                f.write_char('r')?;
+            }
+            if allowed.has(write) {
+                f.write_char('w')?;
+            }
+            if allowed.has(exec) {
+                f.write_char('x')?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Mode> for Umask {
+    /// Only the permission bits of the mode are kept,
+    /// as the umask can't hold extra permission bits.
+    fn from(mode: Mode) -> Self {
+        Self {
+            mode: mode & Mode::all(),
+        }
+    }
+}
+
+impl From<Umask> for Mode {
+    fn from(umask: Umask) -> Self {
+        umask.mode
+    }
+}
+
+impl Umask {
+    /// Return the mode of the masked permissions
+    #[inline(always)]
+    pub const fn mode(self) -> Mode {
+        self.mode
+    }
+    /// Return the umask of the current process.
+    ///
+    /// On Linux, it's read from `/proc/self/status`, which doesn't
+    /// modify it. When this isn't possible (older kernels, other
+    /// unix systems), the umask is read by setting it then immediately
+    /// restoring it, which is racy if other threads create files
+    /// at the same time.
+    ///
+    /// On non unix platforms, return an empty umask.
+    pub fn current() -> Self {
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(umask) = std::fs::read_to_string("/proc/self/status")
+                .ok()
+                .and_then(|status| parse_proc_status(&status))
+            {
+                return umask;
+            }
+        }
+        #[cfg(unix)]
+        {
+            let previous = Self::new(0).set();
+            previous.set();
+            previous
+        }
+        #[cfg(not(unix))]
+        Self::default()
+    }
+    /// Make this umask the one of the current process, and
+    /// return the previous one.
+    ///
+    /// This has no effect on non unix platforms.
+    pub fn set(self) -> Self {
+        #[cfg(unix)]
+        {
+            let previous = unsafe { libc::umask(u32::from(self.mode) as libc::mode_t) };
+            Self::new(previous as u32)
+        }
+        #[cfg(not(unix))]
+        Self::default()
+    }
+    /// Make this umask the one of the current process until
+    /// the returned guard is dropped.
+    ///
+    /// ```
+    /// use umask::*;
+    ///
+    /// let before = Umask::current();
+    /// {
+    ///     let _guard = Umask::from(Mode::from(0o077)).set_guarded();
+    ///     // files created here are only accessible to their owner
+    /// }
+    /// assert_eq!(Umask::current(), before);
+    /// ```
+    pub fn set_guarded(self) -> UmaskGuard {
+        UmaskGuard {
+            previous: self.set(),
+        }
+    }
+    /// Return the mode a file created by open(2) with `O_CREAT`
+    /// and the `requested` mode gets (when the parent directory
+    /// has no default ACL).
+    ///
+    /// ```
+    /// use umask::*;
+    ///
+    /// let umask = Umask::from(Mode::from(0o022));
+    /// assert_eq!(umask.file_creation_mode(Mode::from(0o666)), Mode::from(0o644));
+    /// ```
+    pub const fn file_creation_mode(self, requested: Mode) -> Mode {
+        requested
+            .without(self.mode)
+            .intersection(Mode::all().with_extra(EXTRA))
+    }
+    /// Return the mode a directory created by mkdir(2) with the
+    /// `requested` mode gets (when the parent directory has no
+    /// default ACL and no setgid bit).
+    ///
+    /// As on Linux, the sticky bit is kept but the setuid and setgid
+    /// bits of the requested mode are ignored.
+    ///
+    /// ```
+    /// use umask::*;
+    ///
+    /// let umask = Umask::from(Mode::from(0o022));
+    /// assert_eq!(umask.dir_creation_mode(Mode::from(0o777)), Mode::from(0o755));
+    /// ```
+    pub const fn dir_creation_mode(self, requested: Mode) -> Mode {
+        requested
+            .without(self.mode)
+            .intersection(Mode::all().with_extra(STICKY))
+    }
+    fn new(value: u32) -> Self {
+        Self::from(Mode::from(value))
+    }
+}
+
+/// Held for writing by the tests changing the umask of the process,
+/// and for reading by the tests depending on it, as tests run in
+/// parallel threads of the same process
+#[cfg(test)]
+pub(crate) static UMASK_LOCK: std::sync::RwLock<()> = std::sync::RwLock::new(());
+
+/// Restores, when dropped, the umask which was the one of the
+/// process before [`Umask::set_guarded`] was called.
+#[must_use = "the previous umask is restored when the guard is dropped"]
+#[derive(Debug)]
+pub struct UmaskGuard {
+    previous: Umask,
+}
+
+impl UmaskGuard {
+    /// Return the umask which will be restored
+    pub fn previous(&self) -> Umask {
+        self.previous
+    }
+}
+
+impl Drop for UmaskGuard {
+    fn drop(&mut self) {
+        self.previous.set();
+    }
+}
+
+/// Read the umask from the content of `/proc/self/status`
+/// (there's no such line before Linux 4.7)
+#[cfg(any(target_os = "linux", test))]
+fn parse_proc_status(status: &str) -> Option<Umask> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Umask:"))
+        .and_then(|value| Mode::parse_octal(value.trim()).ok())
+        .map(Umask::from)
+}
+
+#[test]
+fn test_parse_proc_status() {
+    let status = "Name:\tcat\nUmask:\t0027\nState:\tR (running)\n";
+    assert_eq!(parse_proc_status(status), Some(Umask::new(0o027)));
+    assert_eq!(parse_proc_status("Name:\tcat\nState:\tR (running)\n"), None);
+}
+
+#[test]
+fn test_creation_modes() {
+    let umask = Umask::new(0o027);
+    assert_eq!(umask.to_string(), "0027");
+    assert_eq!(format!("{:#}", umask), "u=rwx,g=rx,o=");
+    assert_eq!(format!("{:#}", Umask::new(0o022)), "u=rwx,g=rx,o=rx");
+    assert_eq!(format!("{:#}", Umask::new(0o777)), "u=,g=,o=");
+    let file = |requested: u32| u32::from(umask.file_creation_mode(Mode::from(requested)));
+    let dir = |requested: u32| u32::from(umask.dir_creation_mode(Mode::from(requested)));
+    assert_eq!(file(0o666), 0o640);
+    assert_eq!(file(0o4777), 0o4750);
+    assert_eq!(dir(0o777), 0o750);
+    assert_eq!(dir(0o1777), 0o1750);
+    assert_eq!(dir(0o6777), 0o750);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_set_guarded() {
+    let _lock = UMASK_LOCK.write().unwrap_or_else(|e| e.into_inner());
+    let before = Umask::current();
+    let wanted = Umask::new(if u32::from(before.mode()) == 0o027 {
+        0o077
+    } else {
+        0o027
+    });
+    {
+        let guard = wanted.set_guarded();
+        assert_eq!(guard.previous(), before);
+        assert_eq!(Umask::current(), wanted);
+    }
+    assert_eq!(Umask::current(), before);
+}
@@ -0,0 +1,200 @@
+use {
+    crate::*,
+    std::{
+        collections::HashSet,
+        fs::{self, Metadata},
+        io,
+        os::unix::fs::MetadataExt,
+        path::{Path, PathBuf},
+    },
+    thiserror::Error,
+};
+
+/// An error met while going over a tree
+#[derive(Debug, Error)]
+pub enum TreeError {
+    /// Reading the metadata of a path or the content of a directory failed
+    #[error("reading {path:?} failed: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    /// Changing the mode of a path failed
+    #[error("{0}")]
+    Chmod(#[from] ChmodError),
+    /// Changing the owner and group of a path failed
+    #[error("chown to {uid}:{gid} of {path:?} failed: {source}")]
+    Chown {
+        path: PathBuf,
+        uid: u32,
+        gid: u32,
+        source: io::Error,
+    },
+    /// The entry isn't in the expected state, for example because
+    /// it was modified since a plan was computed
+    #[error("{path:?} is {found} instead of {expected}")]
+    Conflict {
+        path: PathBuf,
+        expected: FullMode,
+        found: FullMode,
+    },
+    /// The path would lead out of the tree, being absolute, going up
+    /// with `..` or through a symbolic link
+    #[error("{path:?} is out of the tree")]
+    OutOfTree { path: PathBuf },
+}
+
+impl TreeError {
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::Read { path, .. } => Some(path),
+            Self::Chmod(e) => e.path.as_deref(),
+            Self::Chown { path, .. } => Some(path),
+            Self::Conflict { path, .. } => Some(path),
+            Self::OutOfTree { path } => Some(path),
+        }
+    }
+}
+
+/// A file or directory met while going over a tree
+#[derive(Debug, Clone)]
+pub struct TreeEntry {
+    pub path: PathBuf,
+    /// 0 for the root
+    pub depth: usize,
+    pub metadata: Metadata,
+}
+
+impl TreeEntry {
+    pub fn full_mode(&self) -> FullMode {
+        FullMode::from(&self.metadata)
+    }
+    pub fn mode(&self) -> Mode {
+        self.full_mode().mode
+    }
+    pub fn file_type(&self) -> FileType {
+        self.metadata.file_type().into()
+    }
+    pub fn is_dir(&self) -> bool {
+        self.metadata.is_dir()
+    }
+}
+
+/// Depth-first iterator over the entries of a tree, with
+/// the entries of a directory sorted by name.
+///
+/// A directory is given before its content is read, so that
+/// a caller changing its mode may make it readable first.
+///
+/// By default, symbolic links aren't followed: they're given
+/// as entries but not traversed.
+#[derive(Debug)]
+pub struct TreeWalker {
+    follow_symlinks: bool,
+    one_file_system: bool,
+    root_dev: Option<u64>,
+    stack: Vec<(PathBuf, usize)>,
+    pending_dir: Option<(PathBuf, usize)>,
+    visited_dirs: HashSet<(u64, u64)>,
+}
+
+impl TreeWalker {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            follow_symlinks: false,
+            one_file_system: false,
+            root_dev: None,
+            stack: vec![(root.as_ref().to_path_buf(), 0)],
+            pending_dir: None,
+            visited_dirs: HashSet::new(),
+        }
+    }
+    /// Follow the symbolic links (loops are detected and not traversed)
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+    /// Don't go into directories on other file systems than the one
+    /// of the root (mount points are still given as entries)
+    pub fn one_file_system(mut self, one_file_system: bool) -> Self {
+        self.one_file_system = one_file_system;
+        self
+    }
+    fn read_pending_dir(&mut self) -> Option<TreeError> {
+        let (dir, depth) = self.pending_dir.take()?;
+        let read_dir = match fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir,
+            Err(source) => return Some(TreeError::Read { path: dir, source }),
+        };
+        let mut children = Vec::new();
+        for entry in read_dir {
+            match entry {
+                Ok(entry) => children.push(entry.path()),
+                Err(source) => {
+                    return Some(TreeError::Read { path: dir, source });
+                }
+            }
+        }
+        children.sort_unstable();
+        self.stack
+            .extend(children.into_iter().rev().map(|path| (path, depth + 1)));
+        None
+    }
+}
+
+impl Iterator for TreeWalker {
+    type Item = Result<TreeEntry, TreeError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.read_pending_dir() {
+            return Some(Err(e));
+        }
+        let (path, depth) = self.stack.pop()?;
+        let metadata = if self.follow_symlinks {
+            fs::metadata(&path)
+        } else {
+            fs::symlink_metadata(&path)
+        };
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(source) => return Some(Err(TreeError::Read { path, source })),
+        };
+        if metadata.is_dir() {
+            let root_dev = *self.root_dev.get_or_insert(metadata.dev());
+            let other_fs = self.one_file_system && metadata.dev() != root_dev;
+            let first_visit = self.visited_dirs.insert((metadata.dev(), metadata.ino()));
+            if first_visit && !other_fs {
+                self.pending_dir = Some((path.clone(), depth));
+            }
+        }
+        Some(Ok(TreeEntry {
+            path,
+            depth,
+            metadata,
+        }))
+    }
+}
+
+#[test]
+fn test_walker() {
+    let root = TestDir::new("walk");
+    fs::create_dir_all(root.join("b/c")).unwrap();
+    fs::write(root.join("a"), "").unwrap();
+    fs::write(root.join("b/c/d"), "").unwrap();
+    std::os::unix::fs::symlink(&root, root.join("b/loop")).unwrap();
+    let names = |walker: TreeWalker| -> Vec<String> {
+        walker
+            .map(|entry| entry.unwrap().path)
+            .map(|path| {
+                path.strip_prefix(&root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    };
+    assert_eq!(
+        names(TreeWalker::new(&root)),
+        vec!["", "a", "b", "b/c", "b/c/d", "b/loop"],
+    );
+    assert_eq!(
+        names(TreeWalker::new(&root).follow_symlinks(true)),
+        vec!["", "a", "b", "b/c", "b/c/d", "b/loop"],
+    );
+}
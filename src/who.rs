@@ -0,0 +1,195 @@
+use {
+    crate::*,
+    std::fmt::{self, Display, Formatter},
+};
+
+/// The access of a user of the database to a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserAccess {
+    pub name: String,
+    pub uid: u32,
+    pub access: Access,
+}
+
+/// The users who can read, write or execute a file, grouped
+/// by the class whose permissions apply to them
+///
+/// Only the mode and ownership of the file are considered: use
+/// [`check_path_access`] to also check the directories leading to it.
+///
+/// ```
+/// use umask::*;
+///
+/// let db = IdentityDb::parse(
+///     "root:x:0:0::/root:/bin/sh\nalice:x:1000:1000::/:/bin/sh\nbob:x:1001:1001::/:/bin/sh\n",
+///     "alice:x:1000:\nbob:x:1001:\nsecrets:x:50:bob\n",
+/// ).unwrap();
+/// let secret = FileStat::new(FileType::Regular, Mode::from(0o640), 1000, 50);
+/// let who = db.who_can_access(&secret);
+/// assert_eq!(who.with_permission(WRITE), vec!["alice", "root"]);
+/// assert_eq!(who.with_permission(READ), vec!["alice", "bob", "root"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhoCanAccess {
+    pub file: FileStat,
+    /// Users to which the user class applies
+    pub owner: Vec<UserAccess>,
+    /// Users to which the group class applies
+    pub group: Vec<UserAccess>,
+    /// Users to which the others class applies
+    pub others: Vec<UserAccess>,
+}
+
+impl WhoCanAccess {
+    /// Return the users to which the given class
+    /// ([`USER`], [`GROUP`] or [`OTHERS`]) applies
+    pub fn by_class(&self, class: Class) -> &[UserAccess] {
+        match class {
+            USER => &self.owner,
+            GROUP => &self.group,
+            _ => &self.others,
+        }
+    }
+    /// Return all the users having access, the owner first
+    pub fn users(&self) -> impl Iterator<Item = &UserAccess> {
+        self.owner
+            .iter()
+            .chain(self.group.iter())
+            .chain(self.others.iter())
+    }
+    /// Return the names of the users having all the wanted
+    /// permissions (a combination of [`READ`], [`WRITE`] and [`EXEC`])
+    pub fn with_permission(&self, wanted: Permission) -> Vec<&str> {
+        self.users()
+            .filter(|user| user.access.allows(wanted))
+            .map(|user| user.name.as_str())
+            .collect()
+    }
+}
+
+impl IdentityDb {
+    /// List the users of the database who can read, write or execute
+    /// the file, root being considered as having all capabilities
+    pub fn who_can_access(&self, file: &FileStat) -> WhoCanAccess {
+        let mut who = WhoCanAccess {
+            file: *file,
+            owner: Vec::new(),
+            group: Vec::new(),
+            others: Vec::new(),
+        };
+        for user in &self.users {
+            let Some(identity) = self.identity(&user.name) else {
+                continue;
+            };
+            let access = AccessEvaluator::privilege_aware(identity).evaluate(file);
+            if !(access.read || access.write || access.exec) {
+                continue;
+            }
+            let user_access = UserAccess {
+                name: user.name.clone(),
+                uid: user.uid,
+                access,
+            };
+            match access.reason.class() {
+                USER => who.owner.push(user_access),
+                GROUP => who.group.push(user_access),
+                _ => who.others.push(user_access),
+            }
+        }
+        who
+    }
+    /// List the users of the database who can read, write or execute
+    /// the file at the given path (following symbolic links)
+    #[cfg(unix)]
+    pub fn who_can_access_path<P: AsRef<std::path::Path>>(
+        &self,
+        path: P,
+    ) -> std::io::Result<WhoCanAccess> {
+        let file = FileStat::read(path)?;
+        Ok(self.who_can_access(&file))
+    }
+}
+
+impl Display for WhoCanAccess {
+    /// Formats the report with one section per class, e.g.
+    ///
+    /// ```text
+    /// owner (rw-):
+    ///   alice rw-
+    /// group (r--):
+    ///   bob r--
+    /// others (---):
+    ///   root rw- (CAP_DAC_OVERRIDE)
+    /// ```
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (title, class, users) in [
+            ("owner", USER, &self.owner),
+            ("group", GROUP, &self.group),
+            ("others", OTHERS, &self.others),
+        ] {
+            let mut class_mode = String::new();
+            for (perm, c) in [(READ, 'r'), (WRITE, 'w'), (EXEC, 'x')] {
+                let granted = self.file.mode.has(Mode::from(class & perm));
+                class_mode.push(if granted { c } else { '-' });
+            }
+            writeln!(f, "{} ({}):", title, class_mode)?;
+            for user in users {
+                write!(f, "  {} {}", user.name, user.access.rwx())?;
+                if let Some(capability) = user.access.bypass {
+                    write!(f, " ({})", capability)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_who_can_access() {
+    let db = IdentityDb::parse(
+        "\
+root:x:0:0::/root:/bin/sh
+alice:x:1000:1000::/home/alice:/bin/sh
+bob:x:1001:1001::/home/bob:/bin/sh
+carol:x:1002:100::/home/carol:/bin/sh
+dave:x:1003:1003::/home/dave:/bin/sh
+",
+        "\
+root:x:0:
+alice:x:1000:
+bob:x:1001:
+dev:x:100:bob
+",
+    )
+    .unwrap();
+    let file = FileStat::new(FileType::Regular, Mode::from(0o754), 1000, 100);
+    let who = db.who_can_access(&file);
+    let names = |users: &[UserAccess]| users.iter().map(|u| u.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(who.by_class(USER)), vec!["alice"]);
+    assert_eq!(names(who.by_class(GROUP)), vec!["bob", "carol"]);
+    assert_eq!(names(who.by_class(OTHERS)), vec!["root", "dave"]);
+    assert_eq!(
+        who.with_permission(EXEC),
+        vec!["alice", "bob", "carol", "root"]
+    );
+    assert_eq!(
+        who.to_string(),
+        "\
+owner (rwx):
+  alice rwx
+group (r-x):
+  bob r-x
+  carol r-x
+others (r--):
+  root rwx (CAP_DAC_OVERRIDE)
+  dave r--
+"
+    );
+    // nobody but root and the owner can access a private file
+    let file = FileStat::new(FileType::Regular, Mode::from(0o600), 1001, 1001);
+    assert_eq!(
+        db.who_can_access(&file).with_permission(READ),
+        vec!["bob", "root"]
+    );
+}